@@ -1,5 +1,6 @@
 #![allow(unused_imports)]
 pub mod deployer;
+pub mod replay;
 use anyhow::{bail, Context, Error};
 use clap::Parser;
 use concordium_rust_sdk::{
@@ -12,7 +13,7 @@ use concordium_rust_sdk::{
     types::{
         smart_contracts::{ContractContext, InvokeContractResult, ModuleReference, WasmModule},
         transactions::{self, send::GivenEnergy, InitContractPayload},
-        ContractAddress
+        AbsoluteBlockHeight, ContractAddress
     },
     v2::{self, BlockIdentifier},
 };
@@ -49,6 +50,35 @@ struct App {
                 ./myPath/default.wasm.v1 --module ./default2.wasm.v1)."
     )]
     module: Vec<PathBuf>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands that replace the example script below.
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Replays the registrations of an existing registry instance into a new
+    /// one, through the `import` entry point of the new instance.
+    Replay {
+        #[clap(long = "source", help = "Index of the existing registry instance.")]
+        source: u64,
+        #[clap(long = "target", help = "Index of the new registry instance.")]
+        target: u64,
+        #[clap(
+            long = "from-height",
+            help = "Block height the existing instance was created at, its events are read \
+                    from there on."
+        )]
+        from_height: u64,
+        #[clap(
+            long = "batch-size",
+            default_value = "20",
+            help = "Number of registrations imported per transaction."
+        )]
+        batch_size: usize,
+        #[clap(long = "seal", help = "Seal the import phase of the new instance afterwards.")]
+        seal: bool,
+    },
 }
 
 
@@ -73,7 +103,7 @@ async fn main() -> Result<(), Error> {
         .endpoint
         .uri()
         .scheme()
-        .map_or(false, |x| *x == concordium_rust_sdk::v2::Scheme::HTTPS)
+        .is_some_and(|x| *x == concordium_rust_sdk::v2::Scheme::HTTPS)
     {
         app.endpoint
             .tls_config(ClientTlsConfig::new())
@@ -82,12 +112,31 @@ async fn main() -> Result<(), Error> {
         app.endpoint
     };
 
-    let mut concordium_client = v2::Client::new(endpoint)
+    let concordium_client = v2::Client::new(endpoint)
         .await
         .context("Unable to establish connection to the node.")?;
 
     let mut deployer = Deployer::new(concordium_client.clone(), &app.key_file)?;
 
+    if let Some(Command::Replay {
+        source,
+        target,
+        from_height,
+        batch_size,
+        seal,
+    }) = app.command
+    {
+        return replay::replay(
+            &mut deployer,
+            ContractAddress::new(source, 0),
+            ContractAddress::new(target, 0),
+            AbsoluteBlockHeight::from(from_height),
+            batch_size,
+            seal,
+        )
+        .await;
+    }
+
     // let mut modules_deployed: Vec<ModuleReference> = Vec::new();
 
     // for contract in app.module {
@@ -148,38 +197,22 @@ async fn main() -> Result<(), Error> {
     let param = RegisterParam {
         tag,
         data: registry,
+        owner: contracts_common::Address::Account(deployer.key.address),
+        entry_point: contracts_common::OwnedEntrypointName::new_unchecked("register".into()),
+        submitter: None,
         expiry_time: Timestamp::from_timestamp_millis(1_000_000_000_000_000_000),
     };
-    let receive_name = OwnedReceiveName::new_unchecked("registry.get_param_hash".to_string());
-    let mut context = ContractContext::new(ContractAddress { index: 10289, subindex: 0 }, receive_name);
-    context.parameter = OwnedParameter::new_unchecked(contracts_common::to_bytes(&param));
-
-    // Get the message hash to be signed.
-    let invoke = concordium_client
-        .invoke_instance(BlockIdentifier::Best, &context)
-        .await?;
-    let invoke_result = match invoke.response {
-        InvokeContractResult::Success {
-            return_value,
-            events: _,
-            used_energy: _,
-        } => return_value,
-        InvokeContractResult::Failure {
-            return_value: _,
-            reason,
-            used_energy: _,
-        } => {
-            println!("{:?}",reason);
-            bail!("Invoke failed");
-
-        } ,
-    };
-
-    let signature = signing_key.sign(&invoke_result.unwrap().value);
+    // Calculate the message hash to be signed, without querying a node.
+    let message_hash = registry::calculate_register_message_hash(
+        &registry::TESTNET_GENESIS_HASH,
+        ContractAddress { index: 10289, subindex: 0 },
+        &param,
+    );
+    let signature = signing_key.sign(&message_hash);
 
     let message = RegisterMessage {
-        signer: alice_public_key,
-        signature: contracts_common::SignatureEd25519(signature.to_bytes()),
+        signer: alice_public_key.into(),
+        signature: contracts_common::SignatureEd25519(signature.to_bytes()).into(),
         message: param,
     };
 
@@ -0,0 +1,253 @@
+use crate::deployer::Deployer;
+use anyhow::{anyhow, bail, Context, Error};
+use concordium_rust_sdk::{
+    common::types::{Amount, Timestamp},
+    smart_contracts::{common as contracts_common, types::OwnedReceiveName},
+    types::{
+        transactions::{send::GivenEnergy, UpdateContractPayload},
+        AbsoluteBlockHeight, ContractAddress,
+    },
+    v2::{self, BlockIdentifier},
+};
+use futures::TryStreamExt;
+use registry::{
+    types::{ImportEntry, ImportParam, Registry},
+    Event,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A function to collect the registrations of the `source` instance from its
+/// `Register` events, in the blocks from `from_height` up to the last finalized
+/// block. Every block is queried on its own, so `from_height` should be the
+/// height the instance was created at. The `Register` events logged before
+/// secp256k1 keys, with an Ed25519 key only, are read as well.
+///
+/// The record of every tag is followed through the `Update`, `Sale`,
+/// `Recovered`, `ChangeExecuted` and `Reassigned` events, and the holder of its
+/// token through the CIS-2 `Mint` and `Transfer` events.
+///
+/// Fails if an event doesn't parse, if an event refers to a tag that was
+/// registered before `from_height`, or if a tag is still frozen, as the import
+/// cannot carry the freeze over.
+pub async fn collect_registrations(
+    client: &mut v2::Client,
+    source: ContractAddress,
+    from_height: AbsoluteBlockHeight,
+) -> Result<Vec<ImportEntry>, Error> {
+    let last_height = client
+        .get_consensus_info()
+        .await?
+        .last_finalized_block_height;
+
+    let mut entries: Vec<ImportEntry> = Vec::new();
+    // The position of every tag in `entries`.
+    let mut positions: BTreeMap<String, usize> = BTreeMap::new();
+    // The tags that are currently frozen.
+    let mut frozen: BTreeSet<String> = BTreeSet::new();
+
+    for height in from_height.height..=last_height.height {
+        let block = BlockIdentifier::AbsoluteHeight(height.into());
+        let summaries: Vec<_> = client
+            .get_block_transaction_events(block)
+            .await?
+            .response
+            .try_collect()
+            .await?;
+
+        let mut events = Vec::new();
+        for summary in &summaries {
+            let Some(logs) = summary.contract_update_logs() else {
+                continue;
+            };
+            for (address, logs) in logs {
+                if address != source {
+                    continue;
+                }
+                for event in logs {
+                    let event = event.parse::<Event>().with_context(|| {
+                        format!(
+                            "Failed to parse an event of transaction {} in block {}.",
+                            summary.hash, height
+                        )
+                    })?;
+                    events.push(event);
+                }
+            }
+        }
+        if events.is_empty() {
+            continue;
+        }
+
+        let slot_time = client.get_block_info(block).await?.response.block_slot_time;
+        let registered_at = Timestamp::from_timestamp_millis(slot_time.timestamp_millis() as u64);
+
+        for event in events {
+            match event {
+                Event::Register(event) => {
+                    positions.insert(event.tag.clone(), entries.len());
+                    entries.push(ImportEntry {
+                        tag: event.tag,
+                        data: Registry::new(
+                            event.public_key,
+                            event.contract_address,
+                            event.provider,
+                        ),
+                        registrar: event.registrar,
+                        owner: event.registrar,
+                        registered_at,
+                    });
+                }
+                Event::LegacyRegister(event) => {
+                    positions.insert(event.tag.clone(), entries.len());
+                    entries.push(ImportEntry {
+                        tag: event.tag,
+                        data: Registry::new(
+                            event.public_key,
+                            event.contract_address,
+                            event.provider,
+                        ),
+                        registrar: event.registrar,
+                        owner: event.registrar,
+                        registered_at,
+                    });
+                }
+                Event::Update(event) => {
+                    entry(&mut entries, &positions, &event.tag)?.data = event.registry;
+                }
+                Event::Sale(event) => {
+                    entry(&mut entries, &positions, &event.tag)?.data = event.registry;
+                }
+                Event::Recovered(event) => {
+                    entry(&mut entries, &positions, &event.tag)?.data.public_key = event.new_key;
+                }
+                Event::ChangeExecuted(event) => {
+                    entry(&mut entries, &positions, &event.tag)?.data = event.registry;
+                }
+                Event::Reassigned(event) => {
+                    let entry = entry(&mut entries, &positions, &event.tag)?;
+                    entry.data = event.registry;
+                    entry.owner = event.owner;
+                    frozen.remove(&event.tag);
+                }
+                Event::Frozen(event) => {
+                    frozen.insert(event.tag);
+                }
+                Event::Unfrozen(event) => {
+                    frozen.remove(&event.tag);
+                }
+                Event::Mint(event) => {
+                    let tag = token_tag(event.token_id.0)?;
+                    entry(&mut entries, &positions, &tag)?.owner = event.owner;
+                }
+                Event::Transfer(event) => {
+                    let tag = token_tag(event.token_id.0)?;
+                    entry(&mut entries, &positions, &tag)?.owner = event.to;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !frozen.is_empty() {
+        let tags: Vec<_> = frozen.into_iter().collect();
+        bail!("Cannot import the frozen tags {}.", tags.join(", "));
+    }
+
+    Ok(entries)
+}
+
+/// Returns the collected entry of `tag`.
+fn entry<'a>(
+    entries: &'a mut [ImportEntry],
+    positions: &BTreeMap<String, usize>,
+    tag: &str,
+) -> Result<&'a mut ImportEntry, Error> {
+    let position = positions
+        .get(tag)
+        .ok_or_else(|| anyhow!("The tag {} was registered before the start height.", tag))?;
+    Ok(&mut entries[*position])
+}
+
+/// Returns the tag of a tag token from its token id.
+fn token_tag(token_id: Vec<u8>) -> Result<String, Error> {
+    String::from_utf8(token_id).context("Failed to read the tag of a token id.")
+}
+
+/// A function to replay the registrations of the `source` instance into the
+/// `target` instance through its `import` entry point.
+///
+/// The registrations are sent in batches of `batch_size` entries per
+/// transaction. If `seal` is set, the import phase of the `target` instance is
+/// sealed afterwards.
+pub async fn replay(
+    deployer: &mut Deployer,
+    source: ContractAddress,
+    target: ContractAddress,
+    from_height: AbsoluteBlockHeight,
+    batch_size: usize,
+    seal: bool,
+) -> Result<(), Error> {
+    println!("\nCollecting registrations....");
+
+    let mut client = deployer.client.clone();
+    let entries = collect_registrations(&mut client, source, from_height).await?;
+
+    println!(
+        "Found {} registrations of contract=({}, {})",
+        entries.len(),
+        source.index,
+        source.subindex
+    );
+
+    for batch in entries.chunks(batch_size.max(1)) {
+        let param = ImportParam {
+            entries: batch.to_vec(),
+        };
+        update(
+            deployer,
+            target,
+            "registry.import",
+            contracts_common::to_bytes(&param),
+        )
+        .await
+        .context("Failed to import a batch of registrations.")?;
+    }
+
+    if seal {
+        update(deployer, target, "registry.seal_import", Vec::new())
+            .await
+            .context("Failed to seal the import phase.")?;
+    }
+
+    Ok(())
+}
+
+/// A function to send an update of the `target` instance, with the energy
+/// estimated by a dry run.
+async fn update(
+    deployer: &mut Deployer,
+    target: ContractAddress,
+    receive_name: &str,
+    message: Vec<u8>,
+) -> Result<(), Error> {
+    let update_payload = UpdateContractPayload {
+        amount: Amount::from_ccd(0),
+        address: target,
+        receive_name: OwnedReceiveName::new_unchecked(receive_name.to_string()),
+        message: message.try_into()?,
+    };
+
+    let mut energy = deployer
+        .estimate_energy(update_payload.clone())
+        .await
+        .context("Failed to estimate the energy.")?;
+
+    // We add 100 energy to be safe.
+    energy.energy += 100;
+
+    deployer
+        .update_contract(update_payload, Some(GivenEnergy::Add(energy)), None)
+        .await?;
+
+    Ok(())
+}
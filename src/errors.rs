@@ -20,7 +20,109 @@ pub enum Error {
     Overflow, // -7
     /// Failed because
     TagAlreadyExists, // -8
-    TagDoesNotExist,        // -9
-    KeyDoesNotExist,        // -10
+    TagDoesNotExist, // -9
+    KeyDoesNotExist, // -10
+    /// Reserved, no longer returned since a key can hold several tags. It is
+    /// kept so that the codes of the later errors stay the same.
     PublicKeyAlreadyExists, // -11
+    /// Failed because the message was intended for a different contract.
+    WrongContract, // -12
+    /// Failed because the signer account of a `permit` message does not exist.
+    #[from(CheckAccountSignatureError)]
+    MissingAccount, // -13
+    /// Failed to log an event.
+    #[from(LogError)]
+    LogFull, // -14
+    /// Failed because the same commitment is already pending.
+    CommitmentAlreadyExists, // -15
+    /// Failed because there is no commitment for the revealed tag.
+    CommitmentDoesNotExist, // -16
+    /// Failed because the commitment is not old enough to be revealed.
+    CommitmentTooNew, // -17
+    /// Failed because the commitment is too old to be revealed.
+    CommitmentExpired, // -18
+    /// Failed because a premium tag can only be registered through an auction.
+    PremiumTag, // -19
+    /// Failed because the tag is not designated as premium.
+    NotPremiumTag, // -20
+    /// Failed because there is already an auction for the tag.
+    AuctionAlreadyExists, // -21
+    /// Failed because there is no auction for the tag.
+    AuctionDoesNotExist, // -22
+    /// Failed because the auction has ended.
+    AuctionEnded, // -23
+    /// Failed because the auction has not ended yet.
+    AuctionNotEnded, // -24
+    /// Failed because the bid is below the reserve price or the highest bid.
+    BidTooLow, // -25
+    /// Failed because only accounts can invoke the entry point.
+    OnlyAccount, // -26
+    /// Failed to transfer CCD.
+    #[from(TransferError)]
+    InvokeTransferError, // -27
+    /// Failed to invoke a contract, e.g. the CIS-2 receive hook.
+    InvokeContractError, // -28
+    /// Failed because the protocol fee is above the whole price.
+    InvalidFee, // -29
+    /// Failed because the attached amount is not the price.
+    WrongAmount, // -30
+    /// Failed because the message is for a different tag.
+    TagMismatch, // -31
+    /// Failed because the threshold is not between one and the number of
+    /// guardians, or there are too many guardians.
+    InvalidGuardians, // -32
+    /// Failed because a recovery to a different key is pending.
+    RecoveryPending, // -33
+    /// Failed because there is no pending recovery for the tag.
+    RecoveryDoesNotExist, // -34
+    /// Failed because the recovery lacks approvals or its window has not
+    /// passed yet.
+    RecoveryNotReady, // -35
+    /// Failed because there is no such pending change for the tag.
+    ChangeDoesNotExist, // -36
+    /// Failed because the delay of the pending change has not passed yet.
+    ChangeNotReady, // -37
+    /// Failed because the tag is frozen.
+    TagFrozen, // -38
+    /// Failed because the tag is not frozen.
+    TagNotFrozen, // -39
+    /// Failed because the import phase is sealed.
+    ImportSealed, // -40
+    /// Failed because the key of the tag can't hold CCD in a CIS-5 wallet.
+    UnsupportedKey, // -41
+    /// Failed because the sender has no escrow for the tag.
+    EscrowDoesNotExist, // -42
+    /// Failed because the escrow can't be refunded yet.
+    EscrowNotRefundable, // -43
+    /// Failed because the sender or the provider registered too many tags in
+    /// the current quota window.
+    QuotaExceeded, // -44
+    /// Failed because the sender registered a tag too recently.
+    RegistrationCooldown, // -45
+    /// Reserved, no longer returned since commitments don't lock their tag. It
+    /// is kept so that the codes of the later errors stay the same.
+    TagCommitted, // -46
+    /// Failed because the entry point of a `permit` message does not support
+    /// `permit`, see `supportsPermit`.
+    UnsupportedPermitEntryPoint, // -47
+    /// Failed because the sale offer is not listed with `list_sale`.
+    OfferNotListed, // -48
+    /// Failed because the pending change is a sale listing, which only `buy`
+    /// executes.
+    ListingNotExecutable, // -49
+    /// Failed because the canonical tag is longer than 128 bytes.
+    TagTooLong, // -50
+    /// Failed because the provider is longer than 128 bytes.
+    ProviderTooLong, // -51
+    /// Failed because the metadata URL is longer than 100 bytes.
+    MetadataUrlTooLong, // -52
+    /// Failed because the registration of a transfer is for a different owner
+    /// than the receiver.
+    OwnerMismatch, // -53
+}
+
+impl<T> From<CallContractError<T>> for Error {
+    fn from(_: CallContractError<T>) -> Self {
+        Error::InvokeContractError
+    }
 }
@@ -4,6 +4,8 @@
 //! to query cis5-wallet standard applicaations. The cis5 wallet
 //! account systems makes use of a [PublicKeyEd25519] key. This key
 //! can be used to deposit and transfer native and token assets on the network.  
+//! Wallets controlled by ECDSA secp256k1 keys, e.g. those of bridged Ethereum
+//! users, are supported as well, see [RegistryKey].
 //!
 //! The overhead of this standard is that users can implement this protocol
 //! on various smart contract and there will be no way for these contracts
@@ -17,17 +19,102 @@
 //! - *public_key*: the public key of the user
 //! - *contract_address*: the smart wallet contract address that the key opearates on
 //! - *provider*: this is a company or an app that manages the contract
+//!
 //! The tags at the point of persisting will append a `.ccd` string to the tags,creating
-//! a unique and deterministic sequence of tags.
+//! a unique and deterministic sequence of tags. See [canonical_tag].
 //!
-//! The three actions in the smart contract that can be taken are:
+//! The main actions in the smart contract that can be taken are:
 //! - *register*: creates a string tag for a given public key
-//! - *get_key*: gets the account details of a key given a tag.
-//! - *get_tag*: does a reverse lookup and gets the tag when given a key.
+//! - *get_key*: gets the account details of a key given a tag, along with the
+//!   registrar and the times it was registered and last updated.
+//! - *get_tag*: does a reverse lookup and gets the primary tag when given a key.
+//! - *resolve*: like *get_key*, but returns `None` for an unknown tag.
+//!
+//! The registry can also be enumerated page by page with *list_tags*, in the
+//! order of registration, so that tools can read every entry straight from the
+//! contract state.
+//! Aggregated counters, such as the number of tags per provider, are kept up to
+//! date on every registration and exposed through *stats*.
+//!
+//! Valuable tags can be registered in two phases to protect them from being
+//! sniped while the registration is pending: *commit* stores a hash of the
+//! tag, the key and a secret salt, and *reveal* registers the tag once the
+//! commitment is old enough.
+//!
+//! Short, valuable tags can be designated as premium by the admin. These are
+//! not registered on a first-come-first-served basis but auctioned off for
+//! CCD, see *start_auction*, *bid* and *settle_auction*.
+//!
+//! Every registered tag is also a CIS-2 non-fungible token, identified by the
+//! bytes of its canonical tag, so tags can be held and traded in any CIS-2
+//! wallet or marketplace. The token is minted to the `owner` signed by the key
+//! in the registration. See *transfer*, *updateOperator*, *balanceOf*,
+//! *operatorOf* and *tokenMetadata*.
+//!
+//! The record of a tag can be changed with *update*, signed by its key. The
+//! key can also authorize operators, such as the contract or account of its
+//! wallet provider, to update the record on its behalf through
+//! *operator_update*, optionally only for a while and for parts of the record.
+//! See *set_tag_operator*.
+//!
+//! A lost key does not have to mean a lost tag: the key can appoint guardians,
+//! keys or addresses, that jointly rebind the tag to a new key. A recovery
+//! only takes effect after a time lock, during which the current key can
+//! cancel it. See *set_guardians*, *approve_recovery*, *cancel_recovery* and
+//! *finalize_recovery*.
+//!
+//! Sensitive changes, a new `contract_address` or moving the tag to a new key
+//! with *rotate_key*, do not take effect immediately. They wait in a
+//! pending-change queue for the change delay, during which the key can cancel
+//! them with *cancel_pending*, and are then executed with *execute_pending*.
+//! A hijacked key therefore can't silently redirect incoming payments.
+//!
+//! CCD can be sent to a tag with *transfer_to_tag*: the registry resolves the
+//! tag and deposits the CCD into the CIS-5 wallet it points to, credited to
+//! its key. Senders pay `buki.ccd` without knowing either.
+//! CIS-2 tokens, such as stablecoins, are sent to a tag by transferring them
+//! to the registry with the tag as `data`. Its *onReceivingCIS2* hook forwards
+//! them to the same wallet.
+//!
+//! CCD can even be paid to a tag that is not registered yet with *escrow*, to
+//! invite a new user. It is deposited into the wallet of the tag once it is
+//! registered, and can be taken back with *refund_escrow* if that takes too
+//! long.
+//!
+//! To keep a single integration from flooding the registry, the admin can
+//! limit the registrations per provider and per sender with *set_quotas*.
+//!
+//! Each message signed by the key of a tag names the entry point it is for,
+//! and optionally the address that may submit it, so a signature can't be
+//! reused for another operation or by another sender. With the `std` feature,
+//! [calculate_register_message_hash] computes the hash that the key signs for
+//! a registration off-chain.
+//!
+//! The registry also commits to all tags and their records in the root of a
+//! Merkle tree, exposed through *merkle_root*, so that light clients such as
+//! mobile wallets can check a cached resolution against a single root. The
+//! [merkle] module verifies the proofs, and builds them from an off-chain copy
+//! of the registry.
+//!
+//! When a new instance replaces an older one, the admin can carry the existing
+//! registrations over with *import*, without asking users to register again,
+//! until the import phase is closed for good with *seal_import*.
+//!
+//! Disputes, such as fraud or trademark claims, are handled by moderators:
+//! they can *freeze* a tag under a case reference, after which it still
+//! resolves but accepts no changes, and resolve the case by either
+//! *unfreeze*-ing it or *reassign*-ing it to another key.
+//!
+//! Holders can also sell their tag at a fixed price without trusting the buyer:
+//! they sign a sale offer, and the buyer pays for it through *buy*, which hands
+//! over the tag and rebinds it to the key of the buyer in one transaction.
 //!
-//! By design the contract exposes only these 3 entry points. A given public key can
-//! only have 1 tag for a lifetime. This means that a key cannot be moved
-//! from one smart contract wallet to another once registered.
+//! Registrations can also be sponsored through the CIS-3 *permit* entry point,
+//! so that users holding no CCD can have a third party submit them.
+//!
+//! A given public key can hold several tags, e.g. a brand tag and a personal
+//! tag on the same wallet key. *get_tags* lists them, and *get_tag* returns the
+//! primary one, which the key chooses with *set_primary*.
 //! Note that the cis5 wallet standard does not aim to replace the account system
 //! on the network but instead a way to quickly onboard users.
 //!
@@ -40,74 +127,1559 @@
 //!
 #![cfg_attr(not(feature = "std"), no_std)]
 pub mod errors;
+pub mod merkle;
 pub mod types;
 use crate::errors::*;
 use crate::types::*;
-use concordium_std::cmp::Ordering;
+use concordium_cis2::*;
 use concordium_std::*;
 use core::fmt::Debug;
 
-// The testnet genesis hash is:
-// 0x4221332d34e1694168c2a0c0b3fd0f273809612cb13d000d5c2e00e85f50f796
-const TESTNET_GENESIS_HASH: [u8; 32] = [
+/// The genesis hash of the chain that signed messages are bound to, see
+/// [calculate_register_message_hash]. The testnet genesis hash is:
+/// 0x4221332d34e1694168c2a0c0b3fd0f273809612cb13d000d5c2e00e85f50f796
+pub const TESTNET_GENESIS_HASH: [u8; 32] = [
     66, 33, 51, 45, 52, 225, 105, 65, 104, 194, 160, 192, 179, 253, 15, 39, 56, 9, 97, 44, 177, 61,
     0, 13, 92, 46, 0, 232, 95, 80, 247, 150,
 ];
 
+/// The standard identifier for the CIS-3: Sponsored Transaction Standard.
+pub const CIS3_STANDARD_IDENTIFIER: StandardIdentifier<'static> =
+    StandardIdentifier::new_unchecked("CIS-3");
+
+/// List of supported standards by this contract.
+const SUPPORTS_STANDARDS: [StandardIdentifier<'static>; 3] = [
+    CIS0_STANDARD_IDENTIFIER,
+    CIS2_STANDARD_IDENTIFIER,
+    CIS3_STANDARD_IDENTIFIER,
+];
+
+/// Every tag is a CIS-2 token, identified by the bytes of its canonical tag.
+pub type ContractTokenId = TokenIdVec;
+
+/// Tags are non-fungible, so an address holds either `0` or `1` of a token.
+pub type ContractTokenAmount = TokenAmountU8;
+
+/// The errors of the CIS-2 entry points, which use the error codes defined by
+/// the standard.
+pub type ContractError = Cis2Error<Error>;
+
+pub type ContractResult<A> = Result<A, ContractError>;
+
+/// The parameter of the CIS-2 `onReceivingCIS2` hook, for tokens of any CIS-2
+/// contract sent with a tag in their `data`.
+pub type OnReceivingTagParams = OnReceivingCis2DataParams<TokenIdVec, TokenAmountU256, String>;
+
+/// The parameter of the CIS-2 `transfer` entry point of any token contract.
+pub type TokenTransferParams = TransferParams<TokenIdVec, TokenAmountU256>;
+
+/// The parameter of the CIS-2 `transfer` entry point.
+pub type TransferParameter = TransferParams<ContractTokenId, ContractTokenAmount>;
+
+/// The parameter of the CIS-2 `balanceOf` entry point.
+pub type ContractBalanceOfQueryParams = BalanceOfQueryParams<ContractTokenId>;
+
+/// The response of the CIS-2 `balanceOf` entry point.
+pub type ContractBalanceOfQueryResponse = BalanceOfQueryResponse<ContractTokenAmount>;
+
+/// The parameter of the CIS-2 `tokenMetadata` entry point.
+pub type ContractTokenMetadataQueryParams = TokenMetadataQueryParams<ContractTokenId>;
+
+/// The `register` entry point.
+const REGISTER_ENTRYPOINT: EntrypointName = EntrypointName::new_unchecked("register");
+
+/// The `commit` entry point.
+const COMMIT_ENTRYPOINT: EntrypointName = EntrypointName::new_unchecked("commit");
+
+/// The `reveal` entry point.
+const REVEAL_ENTRYPOINT: EntrypointName = EntrypointName::new_unchecked("reveal");
+
+/// The CIS-5 `depositCcd` entry point of a smart contract wallet.
+const DEPOSIT_CCD_ENTRYPOINT: EntrypointName = EntrypointName::new_unchecked("depositCcd");
+
+/// The CIS-5 `depositCis2Tokens` entry point of a smart contract wallet.
+const DEPOSIT_CIS2_TOKENS_ENTRYPOINT: &str = "depositCis2Tokens";
+
+/// List of entry points that can be invoked through `permit`.
+/// The CIS-2 `transfer` entry point.
+const TRANSFER_ENTRYPOINT: EntrypointName = EntrypointName::new_unchecked("transfer");
+/// The CIS-2 `updateOperator` entry point.
+const UPDATE_OPERATOR_ENTRYPOINT: EntrypointName = EntrypointName::new_unchecked("updateOperator");
+
+/// The entry points that can be invoked through `permit`.
+const SUPPORTS_PERMIT_ENTRYPOINTS: [EntrypointName; 5] = [
+    REGISTER_ENTRYPOINT,
+    COMMIT_ENTRYPOINT,
+    REVEAL_ENTRYPOINT,
+    TRANSFER_ENTRYPOINT,
+    UPDATE_OPERATOR_ENTRYPOINT,
+];
+
+/// The minimum age of a commitment before it can be revealed. This is what
+/// keeps the revealed tag from being sniped by a commitment made after it was
+/// seen.
+const MIN_COMMITMENT_AGE: Duration = Duration::from_minutes(1);
+
+/// The maximum age of a commitment, after which it can no longer be revealed.
+const MAX_COMMITMENT_AGE: Duration = Duration::from_days(1);
+
+/// The time a recovery has to wait after its first approval before it can be
+/// finalized, which gives the current key time to cancel it.
+const RECOVERY_WINDOW: Duration = Duration::from_days(2);
+
+/// The time CCD paid to an unregistered tag stays in escrow before the sender
+/// can take it back.
+const ESCROW_TIMEOUT: Duration = Duration::from_days(30);
+
+/// The quota window of a new instance, see `set_quotas`.
+const DEFAULT_QUOTA_WINDOW: Duration = Duration::from_days(1);
+
+/// The change delay of a new instance, see `set_change_delay`.
+const DEFAULT_CHANGE_DELAY: Duration = Duration::from_days(1);
+
+/// The maximum number of guardians of a key.
+const MAX_GUARDIANS: usize = 10;
+
+/// The protocol fee is given in basis points of the price, so it can be at most
+/// `10_000`, which is the whole price.
+const MAX_PROTOCOL_FEE: u16 = 10_000;
+
+/// The maximum number of tags returned by a single `list_tags` query.
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// The maximum length in bytes of a canonical tag. Together with
+/// `MAX_METADATA_URL_LENGTH`, it keeps the `TokenMetadata` event of a tag
+/// token, which holds its token id and the token id in hex, within the 512
+/// bytes an event can take.
+const MAX_TAG_LENGTH: usize = 128;
+
+/// The maximum length in bytes of the provider of a record, so that the events
+/// that carry a whole record, such as `Sale`, fit as well.
+const MAX_PROVIDER_LENGTH: usize = 128;
+
+/// The maximum length in bytes of the base of the token metadata URLs, see
+/// `set_metadata_url`.
+const MAX_METADATA_URL_LENGTH: usize = 100;
+
 /// The state of the smart contract.
 #[derive(Serial, DeserialWithState)]
 #[concordium(state_parameter = "S")]
 pub struct State<S = StateApi> {
-    // Add fields to this type to hold state in the smart contract.
-    // This field is just an example.
-    registry: StateMap<String, Registry, S>,
-    lookup: StateMap<PublicKeyEd25519, String, S>,
+    /// The registered tags, keyed by their [TagKey].
+    registry: StateMap<TagKey, RegistryEntry, S>,
+    /// The tags of each key, see `get_tag` and `get_tags`.
+    lookup: StateMap<RegistryKey, KeyTags, S>,
+    /// Global counters, see the `stats` view.
+    stats: RegistryStats,
+    /// The keys of the tags by their position in the order of registration,
+    /// see `list_tags`.
+    tags_in_order: StateMap<u64, TagKey, S>,
+    /// The number of tags registered through each provider.
+    tags_per_provider: StateMap<String, u64, S>,
+    /// The number of tags registered under each namespace.
+    tags_per_namespace: StateMap<String, u64, S>,
+    /// The next nonce of each account signing `permit` messages.
+    nonces_registry: StateMap<AccountAddress, u64, S>,
+    /// The pending commitments of the commit–reveal registration flow, keyed
+    /// by the commitment hash.
+    commitments: StateMap<[u8; 32], Commitment, S>,
+    /// The account that administers the registry, initially the account that
+    /// created the instance.
+    admin: AccountAddress,
+    /// The [TagKey]s of the tags that can only be registered through an
+    /// auction.
+    premium_tags: StateSet<TagKey, S>,
+    /// The running auctions, keyed by the [TagKey] of their tag.
+    auctions: StateMap<TagKey, Auction, S>,
+    /// The CIS-2 operators, as pairs of owner and operator.
+    operators: StateSet<(Address, Address), S>,
+    /// The fee taken by the admin from every `buy`, in basis points of the
+    /// price.
+    protocol_fee: u16,
+    /// The base of the metadata URLs of the tag tokens, see
+    /// `set_metadata_url`.
+    metadata_url: String,
+    /// The operators of each key, which may update the record of its tag.
+    tag_operators: StateMap<(RegistryKey, Address), OperatorAuthorization, S>,
+    /// The guardians of each key, which can recover its tag.
+    guardians: StateMap<RegistryKey, GuardianConfig, S>,
+    /// The pending recoveries, keyed by the [TagKey] of their tag.
+    recoveries: StateMap<TagKey, Recovery, S>,
+    /// The nonce that guardians sign to approve a recovery of each tag, keyed
+    /// by the [TagKey] of the tag. It is bumped whenever a recovery ends, so that
+    /// approvals can not be replayed.
+    recovery_nonces: StateMap<TagKey, u64, S>,
+    /// The time sensitive changes wait in the pending-change queue before they
+    /// can be executed.
+    change_delay: Duration,
+    /// The pending-change queue of each tag, keyed by the [TagKey] of the tag.
+    pending_changes: StateMap<TagKey, Vec<PendingChange>, S>,
+    /// The id of the next pending change.
+    next_change_id: u64,
+    /// The addresses with the dispute role, which can freeze tags.
+    moderators: StateSet<Address, S>,
+    /// Whether the import phase is over, see `seal_import`.
+    import_sealed: bool,
+    /// The nodes of the Merkle tree over all tags and their records, keyed by
+    /// their depth and prefix, see [merkle].
+    merkle_nodes: StateMap<(u16, merkle::Hash), merkle::MerkleNode, S>,
+    /// The whole Merkle tree, `None` while the registry is empty.
+    merkle_top: Option<merkle::MerkleChild>,
+    /// The CCD paid to tags that are not registered yet, keyed by the
+    /// [TagKey] of the tag.
+    escrows: StateMap<TagKey, Vec<Escrow>, S>,
+    /// The anti-spam limits on registrations.
+    quotas: Quotas,
+    /// The registrations through each provider in the current quota window.
+    provider_usage: StateMap<String, Usage, S>,
+    /// The registrations by each sender in the current quota window.
+    sender_usage: StateMap<Address, Usage, S>,
 }
 
 impl State {
-    fn register(&mut self, tag: String, data: Registry) -> RegistryResult<()> {
+    /// Registers `data` under the canonical `tag` with the key `tag_key` on
+    /// behalf of `registrar`, with `owner` holding its token.
+    fn register(
+        &mut self,
+        tag: String,
+        tag_key: TagKey,
+        data: Registry,
+        registrar: Address,
+        owner: Address,
+        now: Timestamp,
+    ) -> RegistryResult<()> {
+        self.insert(
+            tag_key,
+            RegistryEntry {
+                tag,
+                registry: data,
+                registrar,
+                owner,
+                nonce: 0,
+                frozen: None,
+                registered_at: now,
+                updated_at: now,
+                position: 0,
+            },
+        )
+    }
+
+    /// Counts a registration through `provider` by `sender` at `now` against
+    /// the quotas.
+    fn count_registration(
+        &mut self,
+        provider: String,
+        sender: Address,
+        now: Timestamp,
+    ) -> RegistryResult<()> {
+        let Quotas {
+            window,
+            per_provider,
+            per_sender,
+            cooldown,
+        } = self.quotas.clone();
+        let sender_usage = self.sender_usage.get(&sender).map(|usage| usage.clone());
+        if let Some(usage) = &sender_usage {
+            ensure!(
+                now.duration_since(usage.last_registration)
+                    .is_some_and(|elapsed| elapsed >= cooldown),
+                Error::RegistrationCooldown
+            );
+        }
+        let sender_usage = count_usage(sender_usage, per_sender, window, now)?;
+        let provider_usage = self
+            .provider_usage
+            .get(&provider)
+            .map(|usage| usage.clone());
+        let provider_usage = count_usage(provider_usage, per_provider, window, now)?;
+        let _ = self.sender_usage.insert(sender, sender_usage);
+        let _ = self.provider_usage.insert(provider, provider_usage);
+        Ok(())
+    }
+
+    /// Inserts a new `entry` under `tag_key`, the key of its tag, at the next
+    /// position of the registration order, keeping the counters and the lookup
+    /// of its key up to date.
+    fn insert(&mut self, tag_key: TagKey, new_entry: RegistryEntry) -> RegistryResult<()> {
+        let tag = new_entry.tag.clone();
+        ensure_fits(&tag, &new_entry.registry)?;
         let Registry {
             public_key,
             contract_address: _,
-            provider: _,
-        } = data.clone();
-        match self.registry.entry(tag.clone().to_lowercase()) {
+            provider,
+        } = new_entry.registry.clone();
+        match self.registry.entry(tag_key) {
             // check if the tag has been created before.
             Entry::Occupied(_) => Err(Error::TagAlreadyExists),
             Entry::Vacant(entry) => {
-                // check if the public key has created a tag for this key before.
-                if self.lookup.get(&public_key).is_some() {
-                    return Err(Error::PublicKeyAlreadyExists);
-                }
-                entry.insert(data);
-                let _ = self.lookup.insert(public_key, tag.to_lowercase());
+                // Tags are never removed, so the count is the next position.
+                let position = self.stats.total_tags;
+                entry.insert(RegistryEntry {
+                    position,
+                    ..new_entry
+                });
+                let _ = self.tags_in_order.insert(position, tag_key);
+                self.stats.total_tags += 1;
+                increment(&mut self.tags_per_provider, provider);
+                increment(&mut self.tags_per_namespace, namespace(&tag).into());
+                link_key(&mut self.lookup, public_key, tag);
                 Ok(())
             }
         }
     }
 
-    fn get(&self, tag: String) -> RegistryResult<Registry> {
+    /// Updates the Merkle tree with the current record of the canonical `tag`,
+    /// after it was registered or its record changed.
+    fn update_merkle_leaf(&mut self, tag: &TagKey, crypto_primitives: &impl HasCryptoPrimitives) {
+        let hash = |bytes: &[u8]| crypto_primitives.hash_sha2_256(bytes).0;
+        let Some(entry) = self.registry.get(tag) else {
+            return;
+        };
+        // The key of the tag in the state is its path in the tree as well.
+        let key = *tag;
+        let leaf = merkle::MerkleChild {
+            hash: merkle::leaf_hash(&entry.tag, &entry.registry, hash),
+            key,
+            depth: None,
+        };
+        drop(entry);
+
+        // The nodes on the path of the tag, from the root down to the subtree
+        // the path ends in. That is a leaf, or a subtree whose tags differ from
+        // the tag above its node.
+        let mut path = Vec::new();
+        let mut end = self.merkle_top.clone();
+        while let Some(merkle::MerkleChild {
+            key: end_key,
+            depth: Some(depth),
+            ..
+        }) = &end
+        {
+            let depth = usize::from(*depth);
+            if merkle::first_difference(&key, end_key).is_some_and(|bit| bit < depth) {
+                break;
+            }
+            let position = (depth as u16, merkle::prefix(&key, depth));
+            let Some(node) = self.merkle_nodes.get(&position).map(|node| node.clone()) else {
+                break;
+            };
+            end = Some(node.child(merkle::bit(&key, depth)).clone());
+            path.push((position, node));
+        }
+        let mut child = match end
+            .and_then(|end| merkle::first_difference(&key, &end.key).map(|depth| (depth, end)))
+        {
+            // A new tag branches off the subtree its path ends in.
+            Some((depth, end)) => {
+                let node = merkle::MerkleNode::new(depth, leaf, end);
+                let position = (depth as u16, merkle::prefix(&key, depth));
+                let child = merkle::MerkleChild {
+                    hash: node.hash(position.0, hash),
+                    key,
+                    depth: Some(position.0),
+                };
+                let _ = self.merkle_nodes.insert(position, node);
+                child
+            }
+            None => leaf,
+        };
+        for (position, mut node) in path.into_iter().rev() {
+            node.set_child(merkle::bit(&key, usize::from(position.0)), child);
+            child = merkle::MerkleChild {
+                hash: node.hash(position.0, hash),
+                key,
+                depth: Some(position.0),
+            };
+            let _ = self.merkle_nodes.insert(position, node);
+        }
+        self.merkle_top = Some(child);
+    }
+
+    /// Makes the canonical `tag` the primary tag of `key`.
+    fn set_primary(&mut self, key: RegistryKey, tag: String) -> RegistryResult<()> {
+        let mut tags = self.lookup.get_mut(&key).ok_or(Error::KeyDoesNotExist)?;
+        ensure!(tags.tags.contains(&tag), Error::UnAuthorized);
+        tags.primary = tag;
+        Ok(())
+    }
+
+    /// Resolves the canonical `tag` to the CIS-5 wallet it points to and the
+    /// key to credit there.
+    fn wallet(&self, tag: &TagKey) -> RegistryResult<(ContractAddress, PublicKeyEd25519)> {
+        let entry = self.get(tag)?;
+        ensure!(entry.frozen.is_none(), Error::TagFrozen);
+        match entry.registry.public_key {
+            RegistryKey::Ed25519(key) => Ok((entry.registry.contract_address, key)),
+            RegistryKey::Secp256k1(_) => Err(Error::UnsupportedKey),
+        }
+    }
+
+    fn get(&self, tag: &TagKey) -> RegistryResult<RegistryEntry> {
         self.registry
-            .get(&tag.to_lowercase())
+            .get(tag)
             .map(|r| r.clone())
             .ok_or(Error::TagDoesNotExist)
     }
 
-    fn get_tag(&self, key: PublicKeyEd25519) -> RegistryResult<String> {
+    /// Stores a commitment. A commitment can only be replaced once it has
+    /// expired.
+    fn commit(
+        &mut self,
+        commitment: [u8; 32],
+        committer: Address,
+        now: Timestamp,
+    ) -> RegistryResult<()> {
+        if let Some(existing) = self.commitments.get(&commitment) {
+            ensure!(
+                existing.age(now) > MAX_COMMITMENT_AGE,
+                Error::CommitmentAlreadyExists
+            );
+        }
+        let _ = self.commitments.insert(
+            commitment,
+            Commitment {
+                committer,
+                committed_at: now,
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes a commitment for it to be revealed, checking its age.
+    fn take_commitment(&mut self, commitment: [u8; 32], now: Timestamp) -> RegistryResult<()> {
+        let commitment = self
+            .commitments
+            .remove_and_get(&commitment)
+            .ok_or(Error::CommitmentDoesNotExist)?;
+        let age = commitment.age(now);
+        ensure!(age >= MIN_COMMITMENT_AGE, Error::CommitmentTooNew);
+        ensure!(age <= MAX_COMMITMENT_AGE, Error::CommitmentExpired);
+        Ok(())
+    }
+
+    /// Ensures that `sender` is the admin of the registry.
+    fn ensure_admin(&self, sender: Address) -> RegistryResult<()> {
+        ensure!(sender.matches_account(&self.admin), Error::UnAuthorized);
+        Ok(())
+    }
+
+    /// Ensures that `sender` has the dispute role.
+    fn ensure_moderator(&self, sender: Address) -> RegistryResult<()> {
+        ensure!(self.moderators.contains(&sender), Error::UnAuthorized);
+        Ok(())
+    }
+
+    /// Ensures that the canonical `tag` exists and is not frozen.
+    fn ensure_unfrozen(&self, tag: &TagKey) -> RegistryResult<()> {
+        let entry = self.registry.get(tag).ok_or(Error::TagDoesNotExist)?;
+        ensure!(entry.frozen.is_none(), Error::TagFrozen);
+        Ok(())
+    }
+
+    /// Freezes the canonical `tag`, or replaces the dispute of a frozen tag.
+    fn freeze(&mut self, tag: &TagKey, freeze: Freeze) -> RegistryResult<()> {
+        let mut entry = self.registry.get_mut(tag).ok_or(Error::TagDoesNotExist)?;
+        entry.frozen = Some(freeze);
+        Ok(())
+    }
+
+    /// Unfreezes the canonical `tag`.
+    fn unfreeze(&mut self, tag: &TagKey) -> RegistryResult<()> {
+        let mut entry = self.registry.get_mut(tag).ok_or(Error::TagDoesNotExist)?;
+        ensure!(entry.frozen.take().is_some(), Error::TagNotFrozen);
+        Ok(())
+    }
+
+    /// Starts an auction for a premium tag that is not registered yet.
+    fn start_auction(
+        &mut self,
+        tag: TagKey,
+        end: Timestamp,
+        reserve_price: Amount,
+        now: Timestamp,
+    ) -> RegistryResult<()> {
+        ensure!(self.premium_tags.contains(&tag), Error::NotPremiumTag);
+        ensure!(self.registry.get(&tag).is_none(), Error::TagAlreadyExists);
+        ensure!(end > now, Error::Expired);
+        match self.auctions.entry(tag) {
+            Entry::Occupied(_) => Err(Error::AuctionAlreadyExists),
+            Entry::Vacant(entry) => {
+                entry.insert(Auction {
+                    end,
+                    reserve_price,
+                    highest_bid: None,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Records a bid on a running auction and returns the bid it outbids, if
+    /// any, for it to be refunded.
+    fn bid(&mut self, tag: &TagKey, bid: Bid, now: Timestamp) -> RegistryResult<Option<Bid>> {
+        let mut auction = self
+            .auctions
+            .get_mut(tag)
+            .ok_or(Error::AuctionDoesNotExist)?;
+        ensure!(now < auction.end, Error::AuctionEnded);
+        ensure!(bid.amount >= auction.reserve_price, Error::BidTooLow);
+        if let Some(highest_bid) = &auction.highest_bid {
+            ensure!(bid.amount > highest_bid.amount, Error::BidTooLow);
+        }
+        Ok(auction.highest_bid.replace(bid))
+    }
+
+    /// Ends an auction and returns its winning bid, if any.
+    fn end_auction(&mut self, tag: &TagKey, now: Timestamp) -> RegistryResult<Option<Bid>> {
+        let auction = self
+            .auctions
+            .remove_and_get(tag)
+            .ok_or(Error::AuctionDoesNotExist)?;
+        ensure!(now >= auction.end, Error::AuctionNotEnded);
+        Ok(auction.highest_bid)
+    }
+
+    /// Holds `escrow` for the canonical `tag` until it is registered.
+    fn escrow(&mut self, tag: TagKey, escrow: Escrow) -> RegistryResult<()> {
+        ensure!(self.registry.get(&tag).is_none(), Error::TagAlreadyExists);
+        self.escrows.entry(tag).or_default().push(escrow);
+        Ok(())
+    }
+
+    /// Removes the escrow of the registered canonical `tag` and returns it
+    /// with the wallet to deposit it to, if its key can hold CCD there.
+    /// Otherwise the escrow stays until its senders take it back.
+    fn release_escrow(
+        &mut self,
+        tag: &TagKey,
+    ) -> Option<(ContractAddress, PublicKeyEd25519, Vec<Escrow>)> {
+        self.escrows.get(tag)?;
+        let (wallet, key) = self.wallet(tag).ok()?;
+        let escrows = self.escrows.remove_and_get(tag)?;
+        Some((wallet, key, escrows))
+    }
+
+    /// Removes the escrow `sender` paid to the canonical `tag` that is
+    /// refundable at `now`, and returns its total amount.
+    fn refund_escrow(
+        &mut self,
+        tag: &TagKey,
+        sender: AccountAddress,
+        now: Timestamp,
+    ) -> RegistryResult<Amount> {
+        let mut escrows = self.escrows.get_mut(tag).ok_or(Error::EscrowDoesNotExist)?;
+        ensure!(
+            escrows.iter().any(|escrow| escrow.sender == sender),
+            Error::EscrowDoesNotExist
+        );
+        let refundable = |escrow: &Escrow| escrow.sender == sender && now >= escrow.refundable_at;
+        let amount: Amount = escrows
+            .iter()
+            .filter(|escrow| refundable(escrow))
+            .map(|escrow| escrow.amount)
+            .sum();
+        ensure!(amount > Amount::zero(), Error::EscrowNotRefundable);
+        escrows.retain(|escrow| !refundable(escrow));
+        let empty = escrows.is_empty();
+        drop(escrows);
+        if empty {
+            self.escrows.remove(tag);
+        }
+        Ok(amount)
+    }
+
+    /// Returns whether `address` is a CIS-2 operator of `owner`.
+    fn is_operator(&self, address: &Address, owner: &Address) -> bool {
+        self.operators.contains(&(*owner, *address))
+    }
+
+    /// Returns the balance of a tag token held by `address`.
+    fn balance(&self, tag: &TagKey, address: &Address) -> ContractResult<ContractTokenAmount> {
+        let entry = self
+            .registry
+            .get(tag)
+            .ok_or(ContractError::InvalidTokenId)?;
+        Ok(TokenAmountU8((entry.owner == *address).into()))
+    }
+
+    /// Transfers `amount` of the tag token from `from` to `to`, rebinding the
+    /// tag to the key of `data` and replacing the rest of its record, as in a
+    /// sale. The guardians of the old key stay behind.
+    fn transfer(
+        &mut self,
+        tag: &TagKey,
+        amount: ContractTokenAmount,
+        from: &Address,
+        to: &Address,
+        data: Registry,
+        now: Timestamp,
+    ) -> ContractResult<()> {
+        let entry = self
+            .registry
+            .get(tag)
+            .ok_or(ContractError::InvalidTokenId)?;
+        ensure!(
+            entry.frozen.is_none(),
+            ContractError::Custom(Error::TagFrozen)
+        );
+        ensure!(
+            amount == TokenAmountU8(1) && entry.owner == *from,
+            ContractError::InsufficientFunds
+        );
+        drop(entry);
+        self.hand_over(tag, *to, data, now)
+            .map_err(ContractError::Custom)?;
+        self.stats.transfers += 1;
+        Ok(())
+    }
+
+    /// Hands a tag held by `seller` over to `buyer`, rebinding it to the key of
+    /// `data` and replacing the rest of its record. The guardians of the old
+    /// key stay behind. Returns the old key.
+    fn sell(
+        &mut self,
+        tag: &TagKey,
+        seller: Address,
+        buyer: Address,
+        data: Registry,
+        now: Timestamp,
+    ) -> RegistryResult<RegistryKey> {
+        ensure!(self.get(tag)?.owner == seller, Error::UnAuthorized);
+        let old_key = self.hand_over(tag, buyer, data, now)?;
+        self.stats.sales += 1;
+        Ok(old_key)
+    }
+
+    /// Hands the canonical `tag` over to `owner`, rebinding it to the key of
+    /// `data` and replacing the rest of its record. The guardians of the old
+    /// key stay behind. Returns the old key.
+    fn hand_over(
+        &mut self,
+        tag: &TagKey,
+        owner: Address,
+        data: Registry,
+        now: Timestamp,
+    ) -> RegistryResult<RegistryKey> {
+        ensure_fits(&self.get(tag)?.tag, &data)?;
+        let (old_key, previous) = self.relink_key(tag, data.public_key, now)?;
+        decrement(&mut self.tags_per_provider, previous.provider);
+        increment(&mut self.tags_per_provider, data.provider.clone());
+        let mut entry = self.registry.get_mut(tag).ok_or(Error::TagDoesNotExist)?;
+        entry.registry = data;
+        entry.owner = owner;
+        Ok(old_key)
+    }
+
+    /// Hands the canonical `tag` over to `owner`, replacing its record with
+    /// `data` and unfreezing it. Pending changes and recoveries of the tag
+    /// lapse. Returns the record it replaces.
+    fn reassign(
+        &mut self,
+        tag: &TagKey,
+        owner: Address,
+        data: Registry,
+        now: Timestamp,
+    ) -> RegistryResult<Registry> {
+        ensure_fits(&self.get(tag)?.tag, &data)?;
+        self.end_recovery(tag);
+        let mut entry = self.registry.get_mut(tag).ok_or(Error::TagDoesNotExist)?;
+        let previous = entry.registry.clone();
+        if data.public_key != previous.public_key {
+            unlink_key(&mut self.lookup, &previous.public_key, &entry.tag);
+            link_key(&mut self.lookup, data.public_key, entry.tag.clone());
+        }
+        self.pending_changes.remove(tag);
+        decrement(&mut self.tags_per_provider, previous.provider.clone());
+        increment(&mut self.tags_per_provider, data.provider.clone());
+        entry.registry = data;
+        entry.owner = owner;
+        entry.frozen = None;
+        entry.updated_at = now;
+        self.stats.reassignments += 1;
+        Ok(previous)
+    }
+
+    /// Checks that a message signed by `signer` for the canonical `tag` uses the
+    /// key and the next nonce of the tag, and consumes the nonce.
+    fn use_nonce(&mut self, tag: &TagKey, signer: RegistryKey, nonce: u64) -> RegistryResult<()> {
+        let mut entry = self.registry.get_mut(tag).ok_or(Error::TagDoesNotExist)?;
+        ensure!(entry.frozen.is_none(), Error::TagFrozen);
+        ensure!(entry.registry.public_key == signer, Error::UnAuthorized);
+        ensure_eq!(entry.nonce, nonce, Error::NonceMismatch);
+        entry.nonce += 1;
+        Ok(())
+    }
+
+    /// Applies `update` to the record of the canonical `tag` and returns the
+    /// updated record.
+    fn update(
+        &mut self,
+        tag: &TagKey,
+        update: RecordUpdate,
+        now: Timestamp,
+    ) -> RegistryResult<Registry> {
+        if let Some(provider) = &update.provider {
+            ensure!(
+                provider.len() <= MAX_PROVIDER_LENGTH,
+                Error::ProviderTooLong
+            );
+        }
+        let mut entry = self.registry.get_mut(tag).ok_or(Error::TagDoesNotExist)?;
+        ensure!(entry.frozen.is_none(), Error::TagFrozen);
+        if let Some(contract_address) = update.contract_address {
+            entry.registry.contract_address = contract_address;
+        }
+        if let Some(provider) = update.provider {
+            let previous = core::mem::replace(&mut entry.registry.provider, provider.clone());
+            decrement(&mut self.tags_per_provider, previous);
+            increment(&mut self.tags_per_provider, provider);
+        }
+        entry.updated_at = now;
+        Ok(entry.registry.clone())
+    }
+
+    /// Authorizes `operator` for the key of the canonical `tag`, or revokes it
+    /// if `authorization` is `None`. Returns the key of the tag.
+    fn set_tag_operator(
+        &mut self,
+        tag: &TagKey,
+        operator: Address,
+        authorization: Option<OperatorAuthorization>,
+    ) -> RegistryResult<RegistryKey> {
+        let public_key = self.get(tag)?.registry.public_key;
+        match authorization {
+            Some(authorization) => {
+                let _ = self
+                    .tag_operators
+                    .insert((public_key, operator), authorization);
+            }
+            None => self.tag_operators.remove(&(public_key, operator)),
+        }
+        Ok(public_key)
+    }
+
+    /// Returns the rights of `operator` over the canonical `tag`, if any.
+    fn tag_operator(&self, tag: &TagKey, operator: Address) -> Option<OperatorAuthorization> {
+        let public_key = self.registry.get(tag)?.registry.public_key;
+        self.tag_operators
+            .get(&(public_key, operator))
+            .map(|authorization| authorization.clone())
+    }
+
+    /// Ensures that `operator` may apply `update` to the canonical `tag`.
+    fn ensure_tag_operator(
+        &self,
+        tag: &TagKey,
+        operator: Address,
+        update: &RecordUpdate,
+        now: Timestamp,
+    ) -> RegistryResult<()> {
+        let authorization = self
+            .tag_operator(tag, operator)
+            .ok_or(Error::UnAuthorized)?;
+        if let Some(until) = authorization.until {
+            ensure!(now <= until, Error::UnAuthorized);
+        }
+        let allows = |scope| authorization.scopes.contains(&scope);
+        ensure!(
+            update.contract_address.is_none() || allows(UpdateScope::ContractAddress),
+            Error::UnAuthorized
+        );
+        ensure!(
+            update.provider.is_none() || allows(UpdateScope::Provider),
+            Error::UnAuthorized
+        );
+        Ok(())
+    }
+
+    /// Sets the guardians of the key of the canonical `tag`, or removes them if
+    /// `config` is `None`. Returns the key of the tag.
+    fn set_guardians(
+        &mut self,
+        tag: &TagKey,
+        config: Option<GuardianConfig>,
+    ) -> RegistryResult<RegistryKey> {
+        let public_key = self.get(tag)?.registry.public_key;
+        match config {
+            Some(config) => {
+                let count = config.guardians.len();
+                ensure!(
+                    count <= MAX_GUARDIANS
+                        && config.threshold >= 1
+                        && config.threshold as usize <= count,
+                    Error::InvalidGuardians
+                );
+                let _ = self.guardians.insert(public_key, config);
+            }
+            None => self.guardians.remove(&public_key),
+        }
+        Ok(public_key)
+    }
+
+    /// Returns the guardians of the key of the canonical `tag`, if any.
+    fn guardians_of(&self, tag: &TagKey) -> Option<GuardianConfig> {
+        let public_key = self.registry.get(tag)?.registry.public_key;
+        self.guardians.get(&public_key).map(|config| config.clone())
+    }
+
+    /// Records the approvals of `approvers` for recovering the canonical `tag`
+    /// to `new_key`, signed with the recovery `nonce` of the tag. The first
+    /// approval starts the recovery. A pending recovery to a different key is
+    /// only replaced once its window has passed without it being finalized.
+    /// Returns the number of approvals of the recovery.
+    fn approve_recovery(
+        &mut self,
+        tag: &TagKey,
+        new_key: RegistryKey,
+        nonce: u64,
+        approvers: &[Guardian],
+        now: Timestamp,
+    ) -> RegistryResult<u32> {
+        self.ensure_unfrozen(tag)?;
+        ensure_eq!(self.recovery_nonce(tag), nonce, Error::NonceMismatch);
+        let config = self.guardians_of(tag).ok_or(Error::UnAuthorized)?;
+        ensure!(!approvers.is_empty(), Error::UnAuthorized);
+        for approver in approvers {
+            ensure!(config.guardians.contains(approver), Error::UnAuthorized);
+        }
+
+        let mut recovery = self.recoveries.entry(*tag).or_insert_with(|| Recovery {
+            new_key,
+            started_at: now,
+            approvals: Vec::new(),
+        });
+        if recovery.new_key != new_key {
+            let stale = recovery.age(now) >= RECOVERY_WINDOW;
+            ensure!(stale, Error::RecoveryPending);
+            *recovery = Recovery {
+                new_key,
+                started_at: now,
+                approvals: Vec::new(),
+            };
+        }
+        for approver in approvers {
+            if !recovery.approvals.contains(approver) {
+                recovery.approvals.push(*approver);
+            }
+        }
+        Ok(recovery.approvals.len() as u32)
+    }
+
+    /// Cancels the pending recovery of the canonical `tag` and returns it.
+    fn cancel_recovery(&mut self, tag: &TagKey) -> RegistryResult<Recovery> {
+        self.end_recovery(tag).ok_or(Error::RecoveryDoesNotExist)
+    }
+
+    /// Removes the pending recovery of the canonical `tag`, if any, and bumps
+    /// the recovery nonce of the tag so that its approvals lapse too.
+    fn end_recovery(&mut self, tag: &TagKey) -> Option<Recovery> {
+        let recovery = self.recoveries.remove_and_get(tag)?;
+        *self.recovery_nonces.entry(*tag).or_insert(0) += 1;
+        Some(recovery)
+    }
+
+    /// Returns the nonce that guardians sign to approve a recovery of the
+    /// canonical `tag`.
+    fn recovery_nonce(&self, tag: &TagKey) -> u64 {
+        self.recovery_nonces.get(tag).map_or(0, |nonce| *nonce)
+    }
+
+    /// Finalizes the pending recovery of the canonical `tag` once it has
+    /// enough approvals of current guardians and its window has passed. The
+    /// tag is rebound to the new key, which takes over the guardians. Returns
+    /// the old key and the updated record.
+    fn finalize_recovery(
+        &mut self,
+        tag: &TagKey,
+        now: Timestamp,
+    ) -> RegistryResult<(RegistryKey, Registry)> {
+        let recovery = self
+            .recoveries
+            .get(tag)
+            .ok_or(Error::RecoveryDoesNotExist)?
+            .clone();
+        self.get(tag)?;
+        let config = self.guardians_of(tag).ok_or(Error::RecoveryNotReady)?;
+        let window_passed = recovery.age(now) >= RECOVERY_WINDOW;
+        // Approvals of guardians that were removed since do not count.
+        let approvals = recovery
+            .approvals
+            .iter()
+            .filter(|approver| config.guardians.contains(approver))
+            .count();
+        ensure!(
+            window_passed && approvals as u32 >= config.threshold,
+            Error::RecoveryNotReady
+        );
+        self.stats.recoveries += 1;
+        self.rebind_key(tag, recovery.new_key, now)
+    }
+
+    /// Rebinds the canonical `tag` to `new_key`, which takes over the guardians
+    /// of the old key. Pending changes and recoveries of the tag lapse.
+    /// Returns the old key and the updated record.
+    fn rebind_key(
+        &mut self,
+        tag: &TagKey,
+        new_key: RegistryKey,
+        now: Timestamp,
+    ) -> RegistryResult<(RegistryKey, Registry)> {
+        let (old_key, registry) = self.relink_key(tag, new_key, now)?;
+        // The guardians move along if the old key is left without tags, and
+        // are shared otherwise, unless the new key has guardians of its own.
+        let config = if self.lookup.get(&old_key).is_none() {
+            self.guardians.remove_and_get(&old_key)
+        } else {
+            self.guardians.get(&old_key).map(|r| r.clone())
+        };
+        if let Some(config) = config {
+            if self.guardians.get(&new_key).is_none() {
+                let _ = self.guardians.insert(new_key, config);
+            }
+        }
+        Ok((old_key, registry))
+    }
+
+    /// Rebinds the canonical `tag` to `new_key`, leaving the guardians of the
+    /// old key behind. Pending changes and recoveries of the tag lapse.
+    /// Returns the old key and the updated record.
+    fn relink_key(
+        &mut self,
+        tag: &TagKey,
+        new_key: RegistryKey,
+        now: Timestamp,
+    ) -> RegistryResult<(RegistryKey, Registry)> {
+        self.ensure_unfrozen(tag)?;
+        self.end_recovery(tag);
+        let mut entry = self.registry.get_mut(tag).ok_or(Error::TagDoesNotExist)?;
+        let old_key = entry.registry.public_key;
+        unlink_key(&mut self.lookup, &old_key, &entry.tag);
+        link_key(&mut self.lookup, new_key, entry.tag.clone());
+        self.pending_changes.remove(tag);
+        entry.registry.public_key = new_key;
+        entry.updated_at = now;
+        Ok((old_key, entry.registry.clone()))
+    }
+
+    /// Adds `change` to the pending-change queue of the canonical `tag`.
+    fn queue_change(
+        &mut self,
+        tag: &TagKey,
+        change: Change,
+        now: Timestamp,
+    ) -> RegistryResult<PendingChange> {
+        self.ensure_unfrozen(tag)?;
+        let executable_at = now.checked_add(self.change_delay).ok_or(Error::Overflow)?;
+        let pending = PendingChange {
+            id: self.next_change_id,
+            change,
+            executable_at,
+        };
+        self.next_change_id += 1;
+        self.pending_changes
+            .entry(*tag)
+            .or_default()
+            .push(pending.clone());
+        Ok(pending)
+    }
+
+    /// Removes the pending change `id` from the queue of the canonical `tag`
+    /// and returns it.
+    fn take_change(&mut self, tag: &TagKey, id: u64) -> RegistryResult<PendingChange> {
+        let mut queue = self
+            .pending_changes
+            .get_mut(tag)
+            .ok_or(Error::ChangeDoesNotExist)?;
+        let index = queue
+            .iter()
+            .position(|pending| pending.id == id)
+            .ok_or(Error::ChangeDoesNotExist)?;
+        let pending = queue.remove(index);
+        let is_empty = queue.is_empty();
+        drop(queue);
+        if is_empty {
+            self.pending_changes.remove(tag);
+        }
+        Ok(pending)
+    }
+
+    /// Executes the pending change `id` of the canonical `tag` once its delay
+    /// has passed. Returns the updated record.
+    fn execute_change(
+        &mut self,
+        tag: &TagKey,
+        id: u64,
+        now: Timestamp,
+    ) -> RegistryResult<Registry> {
+        let pending = self.take_change(tag, id)?;
+        ensure!(now >= pending.executable_at, Error::ChangeNotReady);
+        match pending.change {
+            Change::ContractAddress(contract_address) => self.update(
+                tag,
+                RecordUpdate {
+                    contract_address: Some(contract_address),
+                    provider: None,
+                },
+                now,
+            ),
+            Change::PublicKey(new_key) => self
+                .rebind_key(tag, new_key, now)
+                .map(|(_, registry)| registry),
+            Change::Sale(_) => Err(Error::ListingNotExecutable),
+        }
+    }
+
+    /// Removes the listing of the sale offer with `offer_hash` from the
+    /// pending-change queue of the canonical `tag`, once its delay has passed.
+    fn take_listing(
+        &mut self,
+        tag: &TagKey,
+        offer_hash: [u8; 32],
+        now: Timestamp,
+    ) -> RegistryResult<()> {
+        let id = self
+            .pending_changes
+            .get(tag)
+            .and_then(|queue| {
+                queue
+                    .iter()
+                    .find(|pending| pending.change == Change::Sale(offer_hash))
+                    .map(|pending| pending.id)
+            })
+            .ok_or(Error::OfferNotListed)?;
+        let pending = self.take_change(tag, id)?;
+        ensure!(now >= pending.executable_at, Error::ChangeNotReady);
+        Ok(())
+    }
+
+    /// Returns the protocol fee taken from a sale at `price`.
+    fn protocol_fee(&self, price: Amount) -> Amount {
+        let fee = u128::from(price.micro_ccd) * u128::from(self.protocol_fee)
+            / u128::from(MAX_PROTOCOL_FEE);
+        // The fee is at most the price, so it fits.
+        Amount::from_micro_ccd(fee as u64)
+    }
+
+    /// Adds or removes `operator` as a CIS-2 operator of `owner`.
+    fn update_operator(&mut self, update: OperatorUpdate, owner: Address, operator: Address) {
+        match update {
+            OperatorUpdate::Add => self.operators.insert((owner, operator)),
+            OperatorUpdate::Remove => self.operators.remove(&(owner, operator)),
+        };
+    }
+
+    /// Returns the metadata URL of the token of the canonical `tag`.
+    fn token_metadata_url(&self, tag: &str) -> MetadataUrl {
+        token_metadata_url(&self.metadata_url, tag)
+    }
+
+    /// Returns the record of a canonical tag with its metadata, if any.
+    fn resolve(&self, tag: &TagKey) -> Option<TagRecord> {
+        let entry = self.registry.get(tag)?.clone();
+        Some(TagRecord::new(entry))
+    }
+
+    fn get_tag(&self, key: RegistryKey) -> RegistryResult<String> {
+        self.get_tags(key).map(|tags| tags.primary)
+    }
+
+    fn get_tags(&self, key: RegistryKey) -> RegistryResult<KeyTags> {
         self.lookup
             .get(&key)
             .map(|r| r.clone())
             .ok_or(Error::KeyDoesNotExist)
     }
+
+    /// Returns up to `limit` tags registered after the `cursor` tag, given by
+    /// its key, or from the first tag if `cursor` is `None`, together with the
+    /// cursor of the next page if there is one. Tags registered between two
+    /// pages come last, so no tag is skipped or repeated.
+    fn list(
+        &self,
+        cursor: Option<TagKey>,
+        limit: u32,
+    ) -> RegistryResult<(Vec<TagRecord>, Option<String>)> {
+        let limit = u64::from(limit.min(MAX_PAGE_SIZE));
+        let start = match cursor {
+            Some(tag) => self.get(&tag)?.position + 1,
+            None => 0,
+        };
+        let end = self.stats.total_tags.min(start + limit);
+        let mut tags = Vec::new();
+        for position in start..end {
+            let tag = self
+                .tags_in_order
+                .get(&position)
+                .map(|tag| *tag)
+                .ok_or(Error::TagDoesNotExist)?;
+            tags.push(TagRecord::new(self.get(&tag)?));
+        }
+        let next_cursor = if end < self.stats.total_tags {
+            tags.last().map(|record| record.tag.clone())
+        } else {
+            None
+        };
+        Ok((tags, next_cursor))
+    }
+
+    /// Returns the global counters along with the counts of the requested
+    /// providers and namespaces. Unknown names are reported with a count of `0`.
+    fn stats(&self, providers: Vec<String>, namespaces: Vec<String>) -> StatsResponse {
+        let count = |map: &StateMap<String, u64, StateApi>, name: String| {
+            let count = map.get(&name).map_or(0, |c| *c);
+            (name, count)
+        };
+        StatsResponse {
+            total_tags: self.stats.total_tags,
+            transfers: self.stats.transfers,
+            sales: self.stats.sales,
+            reassignments: self.stats.reassignments,
+            recoveries: self.stats.recoveries,
+            providers: providers
+                .into_iter()
+                .map(|p| count(&self.tags_per_provider, p))
+                .collect(),
+            namespaces: namespaces
+                .into_iter()
+                .map(|n| count(&self.tags_per_namespace, n))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the canonical form of a tag, which is how it is stored and logged.
+/// The tag is lowercased and the `.ccd` suffix is appended if it is missing,
+/// so `Buki`, `buki` and `BUKI.ccd` all map to `buki.ccd`.
+pub fn canonical_tag(tag: &str) -> String {
+    let mut tag = tag.to_lowercase();
+    if !tag.ends_with(".ccd") {
+        tag.push_str(".ccd");
+    }
+    tag
+}
+
+/// Returns the [TagKey] of a canonical tag, the SHA-256 hash of the tag. The
+/// tags are kept in the contract state under their key, which has a fixed
+/// size however long the tag is.
+pub fn tag_key(tag: &str, crypto_primitives: &impl HasCryptoPrimitives) -> TagKey {
+    crypto_primitives.hash_sha2_256(tag.as_bytes()).0
+}
+
+/// Returns the tag that a token id refers to.
+fn token_tag(token_id: &ContractTokenId) -> ContractResult<String> {
+    String::from_utf8(token_id.0.clone()).map_err(|_| ContractError::InvalidTokenId)
+}
+
+/// Returns the token id of a canonical tag.
+pub fn tag_token_id(tag: &str) -> ContractTokenId {
+    TokenIdVec(tag.as_bytes().to_vec())
+}
+
+/// Checks that the canonical `tag` and the provider of its record are short
+/// enough for the events they are logged in.
+fn ensure_fits(tag: &str, registry: &Registry) -> RegistryResult<()> {
+    ensure!(tag.len() <= MAX_TAG_LENGTH, Error::TagTooLong);
+    ensure!(
+        registry.provider.len() <= MAX_PROVIDER_LENGTH,
+        Error::ProviderTooLong
+    );
+    Ok(())
+}
+
+/// Returns the URL of the CIS-2 metadata of a tag token, the `base` URL set
+/// with `set_metadata_url` followed by the token id in hex. The metadata is
+/// served from there, generated from the record of the tag with
+/// [token_metadata_json]. The URL doesn't embed the record, so it stays short
+/// enough for the `TokenMetadata` events, which can be at most 512 bytes.
+pub fn token_metadata_url(base: &str, tag: &str) -> MetadataUrl {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut url = String::with_capacity(base.len() + 2 * tag.len());
+    url.push_str(base);
+    for byte in tag.bytes() {
+        url.push(HEX[usize::from(byte >> 4)] as char);
+        url.push(HEX[usize::from(byte & 0xf)] as char);
+    }
+    MetadataUrl { url, hash: None }
+}
+
+/// Generates the CIS-2 metadata JSON of a tag token from its [Registry]
+/// record, as served under its [token_metadata_url].
+pub fn token_metadata_json(tag: &str, registry: &Registry) -> String {
+    let mut json = String::from("{\"name\":");
+    push_json_string(&mut json, tag);
+    json.push_str(",\"unique\":true,\"attributes\":[");
+    let attributes = [
+        ("public_key", registry.public_key.to_string()),
+        (
+            "contract_address",
+            format!(
+                "<{},{}>",
+                registry.contract_address.index, registry.contract_address.subindex
+            ),
+        ),
+        ("provider", registry.provider.clone()),
+    ];
+    for (i, (name, value)) in attributes.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str("{\"type\":\"string\",\"name\":");
+        push_json_string(&mut json, name);
+        json.push_str(",\"value\":");
+        push_json_string(&mut json, value);
+        json.push('}');
+    }
+    json.push_str("]}");
+    json
+}
+
+/// Appends `value` to `out` as a quoted JSON string.
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Increments the counter stored under `key`, starting from `0`.
+fn increment(counters: &mut StateMap<String, u64, StateApi>, key: String) {
+    *counters.entry(key).or_insert(0) += 1;
+}
+
+/// Counts a registration at `now` against the `usage` of a provider or a
+/// sender, starting a new window once the current one is over.
+fn count_usage(
+    usage: Option<Usage>,
+    limit: Option<u64>,
+    window: Duration,
+    now: Timestamp,
+) -> RegistryResult<Usage> {
+    let mut usage = match usage {
+        Some(usage)
+            if now
+                .duration_since(usage.window_start)
+                .is_some_and(|age| age < window) =>
+        {
+            usage
+        }
+        _ => Usage {
+            window_start: now,
+            registrations: 0,
+            last_registration: now,
+        },
+    };
+    if let Some(limit) = limit {
+        ensure!(usage.registrations < limit, Error::QuotaExceeded);
+    }
+    usage.registrations += 1;
+    usage.last_registration = now;
+    Ok(usage)
+}
+
+/// Decrements the counter stored under `key`, removing it once it reaches `0`.
+fn decrement(counters: &mut StateMap<String, u64, StateApi>, key: String) {
+    match counters.get(&key).map(|count| *count) {
+        Some(count) if count > 1 => {
+            let _ = counters.insert(key, count - 1);
+        }
+        Some(_) => counters.remove(&key),
+        None => {}
+    }
+}
+
+/// Adds the canonical `tag` to the tags of `key`. It becomes the primary tag
+/// if it is the first one.
+fn link_key(lookup: &mut StateMap<RegistryKey, KeyTags, StateApi>, key: RegistryKey, tag: String) {
+    match lookup.entry(key) {
+        Entry::Occupied(mut entry) => entry.modify(|tags| tags.tags.push(tag)),
+        Entry::Vacant(entry) => {
+            entry.insert(KeyTags {
+                primary: tag.clone(),
+                tags: vec![tag],
+            });
+        }
+    }
+}
+
+/// Removes the canonical `tag` from the tags of `key`. If it was the primary
+/// tag, the oldest remaining tag takes its place.
+fn unlink_key(
+    lookup: &mut StateMap<RegistryKey, KeyTags, StateApi>,
+    key: &RegistryKey,
+    tag: &String,
+) {
+    let Some(mut tags) = lookup.get(key).map(|r| r.clone()) else {
+        return;
+    };
+    tags.tags.retain(|t| t != tag);
+    match tags.tags.first() {
+        None => lookup.remove(key),
+        Some(first) => {
+            if &tags.primary == tag {
+                tags.primary = first.clone();
+            }
+            let _ = lookup.insert(*key, tags);
+        }
+    }
+}
+
+/// Returns the namespace of a canonical tag, that is everything after its
+/// first label. The namespace of `buki.ccd` is `ccd` and the namespace of
+/// `pay.buki.ccd` is `buki.ccd`.
+fn namespace(tag: &str) -> &str {
+    tag.split_once('.').map_or(tag, |(_, namespace)| namespace)
 }
 
 /// Tagged events to be serialized for the event log.
+///
+/// Every change to the state is logged by one event of the registry and tags
+/// are always logged in their canonical form, so replaying the event log in
+/// order is enough to rebuild the tags and their records. Changes to a tag
+/// token are also logged by the CIS-2 events of the token, right after the
+/// event of the registry:
+///
+/// - a registration, by `Register`, is followed by `Mint` and `TokenMetadata`.
+/// - a sale, by `Sale`, and a reassignment, by `Reassigned`, are followed by
+///   `Transfer` and `TokenMetadata`.
+/// - a recovery, by `Recovered`, an executed change, by `ChangeExecuted`, and
+///   an update of the record, by `Update`, are followed by `TokenMetadata`.
+///
+/// A `permit` message and a sale offer use the nonce of their signer, which is
+/// logged by the CIS-3 `Nonce` event after the events of the change.
+///
+/// The tags below are part of the public interface and are never renumbered
+/// or reused, and the layout of an event never changes. An event that needs a
+/// new layout gets a new tag, and the old tag is retired:
+///
+/// | Tag | Event      |
+/// |-----|------------|
+/// | 28  | `LegacyRegister`, retired |
+/// | 29  | `Commit`   |
+/// | 30  | `Reveal`   |
+/// | 31  | `PremiumTag` |
+/// | 32  | `AuctionStarted` |
+/// | 33  | `Bid`      |
+/// | 34  | `AuctionSettled` |
+/// | 35  | `Sale`     |
+/// | 36  | `ProtocolFee` |
+/// | 37  | `Update`   |
+/// | 38  | `TagOperator` |
+/// | 39  | `Guardians` |
+/// | 40  | `RecoveryApproved` |
+/// | 41  | `RecoveryCancelled` |
+/// | 42  | `Recovered` |
+/// | 43  | `ChangeQueued` |
+/// | 44  | `ChangeCancelled` |
+/// | 45  | `ChangeExecuted` |
+/// | 46  | `ChangeDelay` |
+/// | 47  | `Moderator` |
+/// | 48  | `Frozen`   |
+/// | 49  | `Unfrozen` |
+/// | 50  | `Reassigned` |
+/// | 51  | `Primary`  |
+/// | 52  | `ImportSealed` |
+/// | 53  | `Escrowed` |
+/// | 54  | `EscrowReleased` |
+/// | 55  | `EscrowRefunded` |
+/// | 56  | `Quotas`   |
+/// | 57  | `Register` |
+/// | 58  | `MetadataUrl` |
+/// | 250 | `Nonce`    |
+/// | 251 | `TokenMetadata` |
+/// | 252 | `UpdateOperator` |
+/// | 254 | `Mint`     |
+/// | 255 | `Transfer` |
+///
+/// New events are numbered upwards from `59`. The tags `250..=255` are
+/// reserved for the events defined by the CIS standards.
 #[derive(Debug, Serial, Deserial, PartialEq, Eq, SchemaType)]
 #[concordium(repr(u8))]
 pub enum Event {
-    /// The event tracks the nonce used in the message that was signed.
+    /// A tag was registered for an Ed25519 key, in the layout from before
+    /// secp256k1 keys. It is no longer logged, see `Register`.
     #[concordium(tag = 28)]
+    LegacyRegister(LegacyRegisterEvent),
+    /// A commitment was made to register a tag.
+    #[concordium(tag = 29)]
+    Commit(CommitEvent),
+    /// A commitment was revealed, it is followed by the `Register` event of
+    /// the tag.
+    #[concordium(tag = 30)]
+    Reveal(RevealEvent),
+    /// A tag was designated as premium or released from it.
+    #[concordium(tag = 31)]
+    PremiumTag(PremiumTagEvent),
+    /// An auction for a premium tag was started.
+    #[concordium(tag = 32)]
+    AuctionStarted(AuctionStartedEvent),
+    /// A bid was placed on an auction.
+    #[concordium(tag = 33)]
+    Bid(BidEvent),
+    /// An auction was settled. If it had a winner, this is followed by the
+    /// `Register` event of the tag.
+    #[concordium(tag = 34)]
+    AuctionSettled(AuctionSettledEvent),
+    /// A tag was bought through a sale offer. This is followed by the CIS-2
+    /// `Transfer` and `TokenMetadata` events of the tag token.
+    #[concordium(tag = 35)]
+    Sale(SaleEvent),
+    /// The protocol fee was changed.
+    #[concordium(tag = 36)]
+    ProtocolFee(ProtocolFeeEvent),
+    /// The record of a tag was updated. This is followed by the CIS-2
+    /// `TokenMetadata` event of the tag token.
+    #[concordium(tag = 37)]
+    Update(UpdateEvent),
+    /// An operator of a tag was authorized or revoked.
+    #[concordium(tag = 38)]
+    TagOperator(TagOperatorEvent),
+    /// The guardians of a tag were set or removed.
+    #[concordium(tag = 39)]
+    Guardians(GuardiansEvent),
+    /// Guardians approved the recovery of a tag.
+    #[concordium(tag = 40)]
+    RecoveryApproved(RecoveryApprovedEvent),
+    /// The pending recovery of a tag was cancelled.
+    #[concordium(tag = 41)]
+    RecoveryCancelled(RecoveryCancelledEvent),
+    /// A tag was recovered to a new key. This is followed by the CIS-2
+    /// `TokenMetadata` event of the tag token.
+    #[concordium(tag = 42)]
+    Recovered(RecoveredEvent),
+    /// A sensitive change was added to the pending-change queue of a tag.
+    #[concordium(tag = 43)]
+    ChangeQueued(ChangeQueuedEvent),
+    /// A pending change was cancelled.
+    #[concordium(tag = 44)]
+    ChangeCancelled(ChangeCancelledEvent),
+    /// A pending change took effect. This is followed by the CIS-2
+    /// `TokenMetadata` event of the tag token.
+    #[concordium(tag = 45)]
+    ChangeExecuted(ChangeExecutedEvent),
+    /// The change delay was changed.
+    #[concordium(tag = 46)]
+    ChangeDelay(ChangeDelayEvent),
+    /// An address was given or stripped of the dispute role.
+    #[concordium(tag = 47)]
+    Moderator(ModeratorEvent),
+    /// A tag was frozen.
+    #[concordium(tag = 48)]
+    Frozen(FrozenEvent),
+    /// A tag was unfrozen.
+    #[concordium(tag = 49)]
+    Unfrozen(UnfrozenEvent),
+    /// A moderator reassigned a tag. This is followed by the CIS-2 `Transfer`
+    /// event of the tag token if its holder changed, and its `TokenMetadata`
+    /// event.
+    #[concordium(tag = 50)]
+    Reassigned(ReassignedEvent),
+    /// A key chose its primary tag.
+    #[concordium(tag = 51)]
+    Primary(PrimaryEvent),
+    /// The import phase was sealed.
+    #[concordium(tag = 52)]
+    ImportSealed(ImportSealedEvent),
+    /// CCD was paid to a tag that is not registered yet.
+    #[concordium(tag = 53)]
+    Escrowed(EscrowedEvent),
+    /// The escrow of a tag was deposited into its wallet after it was
+    /// registered.
+    #[concordium(tag = 54)]
+    EscrowReleased(EscrowReleasedEvent),
+    /// A sender took back the CCD it paid to a tag that was not registered in
+    /// time.
+    #[concordium(tag = 55)]
+    EscrowRefunded(EscrowRefundedEvent),
+    /// The quotas on registrations were changed.
+    #[concordium(tag = 56)]
+    Quotas(QuotasEvent),
+    /// A tag was registered.
+    #[concordium(tag = 57)]
     Register(RegisterEvent),
+    /// The base of the token metadata URLs was changed.
+    #[concordium(tag = 58)]
+    MetadataUrl(MetadataUrlEvent),
+    /// A `permit` message or a sale offer was executed, as defined by CIS-3.
+    #[concordium(tag = 250)]
+    Nonce(NonceEvent),
+    /// The metadata of a tag token was set, as defined by CIS-2.
+    #[concordium(tag = 251)]
+    TokenMetadata(TokenMetadataEvent<ContractTokenId>),
+    /// An operator was added or removed, as defined by CIS-2.
+    #[concordium(tag = 252)]
+    UpdateOperator(UpdateOperatorEvent),
+    /// A tag token was minted on registration, as defined by CIS-2.
+    #[concordium(tag = 254)]
+    Mint(MintEvent<ContractTokenId, ContractTokenAmount>),
+    /// A tag token was transferred, as defined by CIS-2.
+    #[concordium(tag = 255)]
+    Transfer(TransferEvent<ContractTokenId, ContractTokenAmount>),
+}
+
+/// Logs the `Register` event of a tag, followed by the CIS-2 `Mint` event of
+/// its token to `owner` and its `TokenMetadata` event with `metadata_url`.
+fn log_registration(
+    logger: &mut Logger,
+    tag: String,
+    data: Registry,
+    registrar: Address,
+    owner: Address,
+    metadata_url: MetadataUrl,
+) -> Result<(), LogError> {
+    let token_id = tag_token_id(&tag);
+    logger.log(&Event::Register(RegisterEvent {
+        tag,
+        contract_address: data.contract_address,
+        public_key: data.public_key,
+        provider: data.provider,
+        registrar,
+    }))?;
+    logger.log(&Event::Mint(MintEvent {
+        token_id: token_id.clone(),
+        amount: TokenAmountU8(1),
+        owner,
+    }))?;
+    logger.log(&Event::TokenMetadata(TokenMetadataEvent {
+        token_id,
+        metadata_url,
+    }))
+}
+
+/// Deposits the escrow of the registered canonical `tag` with the key
+/// `tag_key` into its wallet, see `escrow`. If the wallet rejects the deposit,
+/// the escrow is kept for its senders to take back with `refund_escrow`, or for
+/// a later `release_escrow`. Returns whether the tag is left without escrow.
+/// Logs the `EscrowReleased` event if the escrow was deposited.
+fn deposit_escrow(
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    tag: &str,
+    tag_key: &TagKey,
+) -> ReceiveResult<bool> {
+    let Some((wallet, key, escrows)) = host.state_mut().release_escrow(tag_key) else {
+        return Ok(host.state().escrows.get(tag_key).is_none());
+    };
+    let amount = escrows.iter().map(|escrow| escrow.amount).sum();
+    // The escrow is removed during the deposit, so the wallet can not release
+    // it twice.
+    if host
+        .invoke_contract(&wallet, &key, DEPOSIT_CCD_ENTRYPOINT, amount)
+        .is_err()
+    {
+        let _ = host.state_mut().escrows.insert(*tag_key, escrows);
+        return Ok(false);
+    }
+    logger.log(&Event::EscrowReleased(EscrowReleasedEvent {
+        tag: tag.into(),
+        amount,
+    }))?;
+    Ok(true)
 }
 
 /// Calculates the message hash from the message bytes.
@@ -118,31 +1690,88 @@ fn calculate_message_hash_from_bytes(
     crypto_primitives: &impl HasCryptoPrimitives,
     ctx: &ReceiveContext,
 ) -> RegistryResult<[u8; 32]> {
-    // We prepend the message with a context string consistent of the genesis_hash
-    // and this contract address.
-    let mut msg_prepend = [0; 32 + 16];
-    msg_prepend[0..32].copy_from_slice(TESTNET_GENESIS_HASH.as_ref());
-    msg_prepend[32..40].copy_from_slice(&ctx.self_address().index.to_le_bytes());
-    msg_prepend[40..48].copy_from_slice(&ctx.self_address().subindex.to_le_bytes());
+    let preimage = message_hash_preimage(&TESTNET_GENESIS_HASH, ctx.self_address(), message_bytes);
 
     // Calculate the message hash.
-    Ok(crypto_primitives
-        .hash_sha2_256(&[&msg_prepend[0..48], &message_bytes].concat())
-        .0)
+    Ok(crypto_primitives.hash_sha2_256(&preimage).0)
 }
 
-/// Validates the message signature and increases the public key nonce.
-///
-/// It rejects if:
-/// - the message is expired.
-/// - the signature is invalid.
-/// - the message hash can not be calculated.
-fn validate_signature<T: Serial + IsMessage>(
-    message: &T,
-    signer: PublicKeyEd25519,
-    signature: SignatureEd25519,
+/// Calculates the hash of a [RegisterParam] that the key of the tag signs,
+/// without a node. It is the hash that the `get_param_hash` view of the
+/// contract at `contract_address` returns, on the chain with `genesis_hash`.
+#[cfg(feature = "std")]
+pub fn calculate_register_message_hash(
+    genesis_hash: &[u8; 32],
+    contract_address: ContractAddress,
+    param: &RegisterParam,
+) -> [u8; 32] {
+    sha256(&message_hash_preimage(
+        genesis_hash,
+        contract_address,
+        &to_bytes(param),
+    ))
+}
+
+/// Returns the SHA-256 hash of `bytes`, as computed by the contract, e.g. to
+/// verify a [merkle::MerkleProof] off-chain.
+#[cfg(feature = "std")]
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    use sha2::Digest;
+    sha2::Sha256::digest(bytes).into()
+}
+
+/// Prepends the message bytes with a context string consisting of the
+/// `genesis_hash` and the contract address, as the signed messages are hashed.
+fn message_hash_preimage(
+    genesis_hash: &[u8; 32],
+    contract_address: ContractAddress,
+    message_bytes: &[u8],
+) -> Vec<u8> {
+    let mut msg_prepend = [0; 32 + 16];
+    msg_prepend[0..32].copy_from_slice(genesis_hash);
+    msg_prepend[32..40].copy_from_slice(&contract_address.index.to_le_bytes());
+    msg_prepend[40..48].copy_from_slice(&contract_address.subindex.to_le_bytes());
+    [&msg_prepend[0..48], message_bytes].concat()
+}
+
+/// Calculates the commitment hash of the commit–reveal registration flow.
+/// The hash covers the canonical tag, the key it is registered for and a
+/// secret salt, see [CommitmentPreimage].
+fn calculate_commitment_hash(
+    tag: String,
+    public_key: RegistryKey,
+    salt: [u8; 32],
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> [u8; 32] {
+    let preimage = CommitmentPreimage {
+        tag: canonical_tag(&tag),
+        public_key,
+        salt,
+    };
+    crypto_primitives.hash_sha2_256(&to_bytes(&preimage)).0
+}
+
+/// Validates the message signature. The nonce of the key is checked and
+/// increased separately, see `State::use_nonce`.
+///
+/// The `sender` is the address the message is submitted by, the signer of the
+/// `permit` message if it comes through `permit`, and otherwise the sender of
+/// the transaction.
+///
+/// It rejects if:
+/// - the message is expired.
+/// - the message is signed for another entry point than `entry_point`.
+/// - the message names a submitter other than `sender`.
+/// - the signature is invalid.
+/// - the message hash can not be calculated.
+fn validate_signature<T: Serial + IsMessage>(
+    message: &T,
+    signer: RegistryKey,
+    signature: RegistrySignature,
     crypto_primitives: &impl HasCryptoPrimitives,
     ctx: &ReceiveContext,
+    sender: Address,
+    entry_point: EntrypointName,
 ) -> RegistryResult<()> {
     // Check that the signature is not expired.
     ensure!(
@@ -150,13 +1779,29 @@ fn validate_signature<T: Serial + IsMessage>(
         Error::Expired
     );
 
+    // Check that the message was signed for this operation and submitter.
+    ensure!(
+        message.entry_point().as_entrypoint_name() == entry_point,
+        Error::WrongEntryPoint
+    );
+    if let Some(submitter) = message.submitter() {
+        ensure!(submitter == sender, Error::UnAuthorized);
+    }
+
     // Calculate the message hash.
     let message_hash: [u8; 32] =
         calculate_message_hash_from_bytes(&to_bytes(&message), crypto_primitives, ctx)?;
 
-    // Check the signature.
-    let valid_signature =
-        crypto_primitives.verify_ed25519_signature(signer, signature, &message_hash);
+    // Check the signature, the key and the signature must use the same scheme.
+    let valid_signature = match (signer, signature) {
+        (RegistryKey::Ed25519(key), RegistrySignature::Ed25519(signature)) => {
+            crypto_primitives.verify_ed25519_signature(key, signature, &message_hash)
+        }
+        (RegistryKey::Secp256k1(key), RegistrySignature::Secp256k1(signature)) => {
+            crypto_primitives.verify_ecdsa_secp256k1_signature(key, signature, message_hash)
+        }
+        _ => false,
+    };
     ensure!(valid_signature, Error::WrongSignature);
 
     Ok(())
@@ -164,12 +1809,44 @@ fn validate_signature<T: Serial + IsMessage>(
 
 /// Creates a new instance of the smart contract.
 #[init(contract = "registry")]
-fn init(_ctx: &InitContext, state_builder: &mut StateBuilder) -> InitResult<State> {
+fn init(ctx: &InitContext, state_builder: &mut StateBuilder) -> InitResult<State> {
     // Create the initial state of the smart contract here.
     // This state can then be used in the other functions.
     Ok(State {
         registry: state_builder.new_map(),
         lookup: state_builder.new_map(),
+        tags_in_order: state_builder.new_map(),
+        stats: RegistryStats::default(),
+        tags_per_provider: state_builder.new_map(),
+        tags_per_namespace: state_builder.new_map(),
+        nonces_registry: state_builder.new_map(),
+        commitments: state_builder.new_map(),
+        admin: ctx.init_origin(),
+        premium_tags: state_builder.new_set(),
+        auctions: state_builder.new_map(),
+        operators: state_builder.new_set(),
+        protocol_fee: 0,
+        metadata_url: String::new(),
+        tag_operators: state_builder.new_map(),
+        guardians: state_builder.new_map(),
+        recoveries: state_builder.new_map(),
+        recovery_nonces: state_builder.new_map(),
+        change_delay: DEFAULT_CHANGE_DELAY,
+        pending_changes: state_builder.new_map(),
+        next_change_id: 0,
+        moderators: state_builder.new_set(),
+        import_sealed: false,
+        merkle_nodes: state_builder.new_map(),
+        merkle_top: None,
+        escrows: state_builder.new_map(),
+        quotas: Quotas {
+            window: DEFAULT_QUOTA_WINDOW,
+            per_provider: None,
+            per_sender: None,
+            cooldown: Duration::from_millis(0),
+        },
+        provider_usage: state_builder.new_map(),
+        sender_usage: state_builder.new_map(),
     })
 }
 
@@ -202,6 +1879,8 @@ fn contract_get_register_message_hash(
 /// - the message is expired.
 /// - the signature is invalid.
 /// - the nonce is wrong.
+/// - the sender is cooling down or used up a quota, see `set_quotas`.
+/// - the canonical tag or the provider is longer than 128 bytes.
 #[receive(
     contract = "registry",
     name = "register",
@@ -219,65 +1898,3046 @@ fn register(
 ) -> ReceiveResult<()> {
     // Parse the parameter.
     let param: RegisterMessage = ctx.parameter_cursor().get()?;
+    register_tag(
+        ctx,
+        host,
+        logger,
+        crypto_primitives,
+        param,
+        ctx.sender(),
+        REGISTER_ENTRYPOINT,
+    )
+}
 
+/// Registers the tag of a signed `RegisterMessage` on behalf of `registrar`,
+/// the message must be signed for `entry_point`. Shared by the `register`,
+/// `reveal` and `permit` entry points.
+fn register_tag(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+    param: RegisterMessage,
+    registrar: Address,
+    entry_point: EntrypointName,
+) -> ReceiveResult<()> {
     let RegisterMessage {
         signer,
         signature,
         message,
     } = param;
     ensure!(
-        signer.cmp(&message.data.public_key) == Ordering::Equal,
+        signer == message.data.public_key,
         Error::WrongSignature.into()
     );
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        registrar,
+        entry_point,
+    )?;
     let RegisterParam {
+        entry_point: _,
+        submitter: _,
         expiry_time: _,
-        mut tag,
+        tag,
         data,
-    } = message.clone();
-    // Validate the signature.
-    validate_signature(&message, signer, signature, crypto_primitives, ctx)?;
-    if !tag.ends_with(".ccd") {
-        tag.push_str(".ccd");
-    }
+        owner,
+    } = message;
+    let tag = canonical_tag(&tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    // Premium tags can only be won in an auction.
+    ensure!(
+        !host.state().premium_tags.contains(&tag_key),
+        Error::PremiumTag.into()
+    );
+    host.state_mut().count_registration(
+        data.provider.clone(),
+        registrar,
+        ctx.metadata().slot_time(),
+    )?;
     // Register tag on chain
-    host.state_mut().register(tag, data)?;
+    host.state_mut().register(
+        tag.clone(),
+        tag_key,
+        data.clone(),
+        registrar,
+        owner,
+        ctx.metadata().slot_time(),
+    )?;
+    host.state_mut()
+        .update_merkle_leaf(&tag_key, crypto_primitives);
 
-    logger.log(&Event::Register(RegisterEvent {
-        tag: message.tag,
-        contract_address: message.data.contract_address,
-        public_key: message.data.public_key,
-        provider: message.data.provider,
-        registrar: ctx.sender(),
+    let metadata_url = host.state().token_metadata_url(&tag);
+    log_registration(logger, tag.clone(), data, registrar, owner, metadata_url)?;
+    deposit_escrow(host, logger, &tag, &tag_key)?;
+
+    Ok(())
+}
+
+/// Commits to registering a tag without disclosing it. The parameter is the
+/// commitment hash of the tag, the key and a secret salt, as returned by
+/// `get_commitment_hash`. The tag is registered later on with `reveal`.
+/// Logs the `Commit` event.
+///
+/// The commitment discloses neither the tag nor the key, so nothing can be
+/// learned from it before the tag is revealed, and the reveal registers the
+/// tag in the same transaction. A commitment doesn't hold the tag back from
+/// being registered otherwise in the meantime.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the same commitment is pending and has not expired.
+#[receive(
+    contract = "registry",
+    name = "commit",
+    parameter = "[u8;32]",
+    error = "Error",
+    enable_logger,
+    mutable
+)]
+fn commit(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let commitment: [u8; 32] = ctx.parameter_cursor().get()?;
+    commit_tag(ctx, host, logger, commitment, ctx.sender())
+}
+
+/// Stores a commitment on behalf of `committer`.
+/// Shared by the `commit` and `permit` entry points.
+fn commit_tag(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    commitment: [u8; 32],
+    committer: Address,
+) -> ReceiveResult<()> {
+    host.state_mut()
+        .commit(commitment, committer, ctx.metadata().slot_time())?;
+    logger.log(&Event::Commit(CommitEvent {
+        commitment,
+        committer,
     }))?;
+    Ok(())
+}
+
+/// Registers a tag that was committed to with `commit`.
+/// The commitment must be at least `MIN_COMMITMENT_AGE` old and at most
+/// `MAX_COMMITMENT_AGE`. Logs the `Reveal` event followed by the `Register`
+/// event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - there is no commitment for the tag, key and salt.
+/// - the commitment is too new or expired.
+/// - the registration itself is rejected, see `register`.
+#[receive(
+    contract = "registry",
+    name = "reveal",
+    parameter = "RevealParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn reveal(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: RevealParam = ctx.parameter_cursor().get()?;
+    reveal_tag(ctx, host, logger, crypto_primitives, param, ctx.sender())
+}
+
+/// Reveals a commitment and registers its tag on behalf of `registrar`.
+/// Shared by the `reveal` and `permit` entry points.
+fn reveal_tag(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+    param: RevealParam,
+    registrar: Address,
+) -> ReceiveResult<()> {
+    let RevealParam { message, salt } = param;
+    let tag = canonical_tag(&message.message.tag);
+    let commitment = calculate_commitment_hash(
+        tag.clone(),
+        message.message.data.public_key,
+        salt,
+        crypto_primitives,
+    );
+    host.state_mut()
+        .take_commitment(commitment, ctx.metadata().slot_time())?;
+    logger.log(&Event::Reveal(RevealEvent { commitment, tag }))?;
+    register_tag(
+        ctx,
+        host,
+        logger,
+        crypto_primitives,
+        message,
+        registrar,
+        REVEAL_ENTRYPOINT,
+    )
+}
+
+/// Helper function to calculate the commitment hash to pass to `commit`.
+/// The input parameter in this function is a `CommitmentPreimage`.
+#[receive(
+    contract = "registry",
+    name = "get_commitment_hash",
+    parameter = "CommitmentPreimage",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_commitment_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: CommitmentPreimage = ctx.parameter_cursor().get()?;
+    Ok(calculate_commitment_hash(
+        param.tag,
+        param.public_key,
+        param.salt,
+        crypto_primitives,
+    ))
+}
+
+/// Designates tags as premium, or releases them from it. Premium tags can not
+/// be registered directly but only be won in an auction.
+/// Logs a `PremiumTag` event for every tag.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+#[receive(
+    contract = "registry",
+    name = "set_premium_tags",
+    parameter = "SetPremiumTagsParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn set_premium_tags(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: SetPremiumTagsParam = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
 
+    for tag in param.tags {
+        let tag = canonical_tag(&tag);
+        let tag_key = tag_key(&tag, crypto_primitives);
+        if param.premium {
+            host.state_mut().premium_tags.insert(tag_key);
+        } else {
+            host.state_mut().premium_tags.remove(&tag_key);
+        }
+        logger.log(&Event::PremiumTag(PremiumTagEvent {
+            tag,
+            premium: param.premium,
+        }))?;
+    }
     Ok(())
 }
 
-/// Get's the registered tag for a user.
-/// The input parameter in this function is a `String`.
+/// Starts an auction for a premium tag. Bids are placed in CCD with `bid`
+/// until the auction ends, after which it is settled with `settle_auction`.
+/// Logs the `AuctionStarted` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+/// - the tag is not premium, is already registered or already auctioned.
+/// - the end of the auction is not in the future.
 #[receive(
     contract = "registry",
-    name = "get_key",
+    name = "start_auction",
+    parameter = "StartAuctionParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn start_auction(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: StartAuctionParam = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
+
+    let tag = canonical_tag(&param.tag);
+    host.state_mut().start_auction(
+        tag_key(&tag, crypto_primitives),
+        param.end,
+        param.reserve_price,
+        ctx.metadata().slot_time(),
+    )?;
+    logger.log(&Event::AuctionStarted(AuctionStartedEvent {
+        tag,
+        end: param.end,
+        reserve_price: param.reserve_price,
+    }))?;
+    Ok(())
+}
+
+/// Bids the attached CCD on the auction of a premium tag. The parameter is the
+/// signed registration of the tag for the key of the bidder, which is
+/// registered if the bid wins. The bid it outbids is refunded.
+/// Logs the `Bid` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not an account.
+/// - the message is expired or the signature is invalid.
+/// - there is no running auction for the tag.
+/// - the bid is below the reserve price or not above the highest bid.
+/// - the provider is longer than 128 bytes.
+#[receive(
+    contract = "registry",
+    name = "bid",
+    parameter = "RegisterMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    payable,
+    mutable
+)]
+fn bid(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: RegisterMessage = ctx.parameter_cursor().get()?;
+    let bidder = match ctx.sender() {
+        Address::Account(account) => account,
+        Address::Contract(_) => bail!(Error::OnlyAccount.into()),
+    };
+    let RegisterMessage {
+        signer,
+        signature,
+        message,
+    } = param;
+    ensure!(
+        signer == message.data.public_key,
+        Error::WrongSignature.into()
+    );
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    ensure_fits(&tag, &message.data)?;
+    let outbid = host.state_mut().bid(
+        &tag_key(&tag, crypto_primitives),
+        Bid {
+            bidder,
+            amount,
+            data: message.data,
+            owner: message.owner,
+        },
+        ctx.metadata().slot_time(),
+    )?;
+    if let Some(outbid) = outbid {
+        host.invoke_transfer(&outbid.bidder, outbid.amount)?;
+    }
+    logger.log(&Event::Bid(BidEvent {
+        tag,
+        bidder,
+        amount,
+    }))?;
+    Ok(())
+}
+
+/// Settles an auction once it has ended, anyone can settle it. The tag is
+/// registered for the winning bid and the bid is paid out to the admin.
+/// If the tag was registered in the meantime, e.g. by `import`, the bid is
+/// refunded instead.
+/// Logs the `AuctionSettled` event, followed by the `Register`, `Mint` and
+/// `TokenMetadata` events of the tag if it has a winner.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - there is no auction for the tag or it has not ended yet.
+#[receive(
+    contract = "registry",
+    name = "settle_auction",
     parameter = "String",
-    error = "Error"
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
 )]
-fn get_key(ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<Registry> {
-    let mut tag: String = ctx.parameter_cursor().get()?;
-    if !tag.ends_with(".ccd") {
-        tag.push_str(".ccd");
+fn settle_auction(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let tag: String = ctx.parameter_cursor().get()?;
+    let tag = canonical_tag(&tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    let now = ctx.metadata().slot_time();
+    let winner = host.state_mut().end_auction(&tag_key, now)?;
+
+    let Some(Bid {
+        bidder,
+        amount,
+        data,
+        owner,
+    }) = winner
+    else {
+        logger.log(&Event::AuctionSettled(AuctionSettledEvent {
+            tag,
+            winner: None,
+            amount: Amount::zero(),
+        }))?;
+        return Ok(());
+    };
+
+    let registrar = Address::Account(bidder);
+    match host
+        .state_mut()
+        .register(tag.clone(), tag_key, data.clone(), registrar, owner, now)
+    {
+        Ok(()) => {
+            host.state_mut()
+                .update_merkle_leaf(&tag_key, crypto_primitives);
+            let admin = host.state().admin;
+            host.invoke_transfer(&admin, amount)?;
+            logger.log(&Event::AuctionSettled(AuctionSettledEvent {
+                tag: tag.clone(),
+                winner: Some(bidder),
+                amount,
+            }))?;
+            let metadata_url = host.state().token_metadata_url(&tag);
+            log_registration(logger, tag.clone(), data, registrar, owner, metadata_url)?;
+            deposit_escrow(host, logger, &tag, &tag_key)?;
+        }
+        Err(_) => {
+            host.invoke_transfer(&bidder, amount)?;
+            logger.log(&Event::AuctionSettled(AuctionSettledEvent {
+                tag,
+                winner: None,
+                amount: Amount::zero(),
+            }))?;
+        }
     }
-    host.state.get(tag)
+    Ok(())
 }
 
-/// Get's the registered tag for a user.
+/// Gets the running auction of a tag, if any.
 /// The input parameter in this function is a `String`.
 #[receive(
     contract = "registry",
-    name = "get_tag",
-    parameter = "PublicKeyEd25519",
-    error = "Error"
+    name = "get_auction",
+    parameter = "String",
+    return_value = "Option<Auction>",
+    error = "Error",
+    crypto_primitives
 )]
-fn get_tag(ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<String> {
-    let key: PublicKeyEd25519 = ctx.parameter_cursor().get()?;
-    host.state.get_tag(key)
+fn get_auction(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<Option<Auction>> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state
+        .auctions
+        .get(&tag_key(&canonical_tag(&tag), crypto_primitives))
+        .map(|auction| auction.clone()))
+}
+
+/// Helper function to calculate the message hash of a `SetPrimaryParam`,
+/// which is what the key of the tag signs.
+#[receive(
+    contract = "registry",
+    name = "get_set_primary_hash",
+    parameter = "SetPrimaryParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_set_primary_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: SetPrimaryParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Makes a tag the primary tag of its key, which `get_tag` returns, with a
+/// message signed by the key. The message can be submitted by anyone.
+/// Logs the `Primary` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or the signature is invalid.
+/// - the tag does not exist, is frozen or is registered for a different key.
+/// - the nonce is wrong.
+#[receive(
+    contract = "registry",
+    name = "set_primary",
+    parameter = "SetPrimaryMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn set_primary(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let SetPrimaryMessage {
+        signer,
+        signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    host.state_mut()
+        .use_nonce(&tag_key(&tag, crypto_primitives), signer, message.nonce)?;
+    host.state_mut().set_primary(signer, tag.clone())?;
+    logger.log(&Event::Primary(PrimaryEvent { key: signer, tag }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of an `UpdateParam`, which
+/// is what the key of the tag signs.
+#[receive(
+    contract = "registry",
+    name = "get_update_hash",
+    parameter = "UpdateParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_update_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: UpdateParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Updates the `contract_address` or the `provider` of a tag with a message
+/// signed by the key of the tag. The message can be submitted by anyone.
+/// A new `contract_address` is queued as a pending change, see
+/// `execute_pending`.
+/// Logs the `ChangeQueued` event for a new `contract_address`, and the `Update`
+/// event followed by the CIS-2 `TokenMetadata` event for a new `provider`.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or the signature is invalid.
+/// - the tag does not exist or is registered for a different key.
+/// - the nonce is wrong.
+/// - the new provider is longer than 128 bytes.
+#[receive(
+    contract = "registry",
+    name = "update",
+    parameter = "UpdateMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn update(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let UpdateMessage {
+        signer,
+        signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state_mut()
+        .use_nonce(&tag_key, signer, message.nonce)?;
+    update_record(
+        ctx,
+        host,
+        logger,
+        crypto_primitives,
+        tag,
+        tag_key,
+        message.update,
+    )
+}
+
+/// Updates the record of a tag on behalf of one of its operators, without a
+/// signature of the key of the tag. The sender must be an operator authorized
+/// for every part of the record that is updated. As with `update`, a new
+/// `contract_address` is queued as a pending change.
+/// Logs the `ChangeQueued` event for a new `contract_address`, and the `Update`
+/// event followed by the CIS-2 `TokenMetadata` event for a new `provider`.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the tag does not exist.
+/// - the sender is not an operator of the tag, its authorization has expired
+///   or does not cover the update.
+/// - the new provider is longer than 128 bytes.
+#[receive(
+    contract = "registry",
+    name = "operator_update",
+    parameter = "OperatorUpdateParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn operator_update(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: OperatorUpdateParam = ctx.parameter_cursor().get()?;
+    let tag = canonical_tag(&param.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state().ensure_tag_operator(
+        &tag_key,
+        ctx.sender(),
+        &param.update,
+        ctx.metadata().slot_time(),
+    )?;
+    update_record(
+        ctx,
+        host,
+        logger,
+        crypto_primitives,
+        tag,
+        tag_key,
+        param.update,
+    )
+}
+
+/// Applies an update to the record of a canonical tag and logs it. A new
+/// `contract_address` is a sensitive change, so it is queued instead.
+/// Shared by the `update` and `operator_update` entry points.
+fn update_record(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+    tag: String,
+    tag_key: TagKey,
+    update: RecordUpdate,
+) -> ReceiveResult<()> {
+    let now = ctx.metadata().slot_time();
+    let RecordUpdate {
+        contract_address,
+        provider,
+    } = update;
+    if let Some(contract_address) = contract_address {
+        let change = Change::ContractAddress(contract_address);
+        let change = host.state_mut().queue_change(&tag_key, change, now)?;
+        logger.log(&Event::ChangeQueued(ChangeQueuedEvent {
+            tag: tag.clone(),
+            change,
+        }))?;
+    }
+    if provider.is_none() {
+        return Ok(());
+    }
+
+    let update = RecordUpdate {
+        contract_address: None,
+        provider,
+    };
+    let registry = host.state_mut().update(&tag_key, update, now)?;
+    host.state_mut()
+        .update_merkle_leaf(&tag_key, crypto_primitives);
+    let token_id = tag_token_id(&tag);
+    let metadata_url = host.state().token_metadata_url(&tag);
+    logger.log(&Event::Update(UpdateEvent {
+        tag,
+        registry,
+        updated_by: ctx.sender(),
+    }))?;
+    logger.log(&Event::TokenMetadata(TokenMetadataEvent {
+        token_id,
+        metadata_url,
+    }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of a `TagOperatorParam`,
+/// which is what the key of the tag signs.
+#[receive(
+    contract = "registry",
+    name = "get_tag_operator_hash",
+    parameter = "TagOperatorParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_tag_operator_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: TagOperatorParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Authorizes an operator to update the record of a tag, or revokes it, with a
+/// message signed by the key of the tag. The authorization can be limited in
+/// time and to parts of the record, see `OperatorAuthorization`. It belongs to
+/// the key, so it lapses once the tag is rebound to a different key.
+/// Logs the `TagOperator` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or the signature is invalid.
+/// - the tag does not exist or is registered for a different key.
+/// - the nonce is wrong.
+#[receive(
+    contract = "registry",
+    name = "set_tag_operator",
+    parameter = "TagOperatorMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn set_tag_operator(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let TagOperatorMessage {
+        signer,
+        signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state_mut()
+        .use_nonce(&tag_key, signer, message.nonce)?;
+    let public_key = host.state_mut().set_tag_operator(
+        &tag_key,
+        message.operator,
+        message.authorization.clone(),
+    )?;
+    logger.log(&Event::TagOperator(TagOperatorEvent {
+        tag,
+        public_key,
+        operator: message.operator,
+        authorization: message.authorization,
+    }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of a `GuardiansParam`, which
+/// is what the key of the tag signs.
+#[receive(
+    contract = "registry",
+    name = "get_guardians_hash",
+    parameter = "GuardiansParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_guardians_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: GuardiansParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Sets the guardians of a tag and how many of them are needed to recover it,
+/// or removes them, with a message signed by the key of the tag. The guardians
+/// belong to the key, so they lapse once the tag is sold to a different key.
+/// Logs the `Guardians` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or the signature is invalid.
+/// - the tag does not exist or is registered for a different key.
+/// - the nonce is wrong.
+/// - the threshold is not between one and the number of guardians, or there
+///   are more than `MAX_GUARDIANS` guardians.
+#[receive(
+    contract = "registry",
+    name = "set_guardians",
+    parameter = "GuardiansMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn set_guardians(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let GuardiansMessage {
+        signer,
+        signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state_mut()
+        .use_nonce(&tag_key, signer, message.nonce)?;
+    let public_key = host
+        .state_mut()
+        .set_guardians(&tag_key, message.config.clone())?;
+    logger.log(&Event::Guardians(GuardiansEvent {
+        tag,
+        public_key,
+        config: message.config,
+    }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of a `RecoveryParam`, which
+/// is what guardian keys sign to approve a recovery.
+#[receive(
+    contract = "registry",
+    name = "get_recovery_hash",
+    parameter = "RecoveryParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_recovery_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: RecoveryParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Approves the recovery of a tag to a new key. The sender approves it if it is
+/// a guardian address, and every signature of a guardian key over the
+/// `RecoveryParam` approves it as well. The first approval starts the recovery
+/// window, after which `finalize_recovery` rebinds the tag once enough
+/// guardians approved it. Until then the current key can cancel it.
+/// Logs the `RecoveryApproved` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or a signature is invalid.
+/// - the tag does not exist or has no guardians.
+/// - there is no approval, or one of the approvers is not a guardian.
+/// - the recovery nonce is wrong, see `get_recovery_nonce`.
+/// - a recovery to a different key is pending and its window has not passed.
+#[receive(
+    contract = "registry",
+    name = "approve_recovery",
+    parameter = "ApproveRecoveryParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn approve_recovery(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let ApproveRecoveryParam {
+        message,
+        signatures,
+    } = ctx.parameter_cursor().get()?;
+    ensure!(
+        message.expiry_time > ctx.metadata().slot_time(),
+        Error::Expired.into()
+    );
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    let mut approvers = Vec::with_capacity(signatures.len() + 1);
+    let sender = Guardian::Address(ctx.sender());
+    let is_guardian = host
+        .state()
+        .guardians_of(&tag_key)
+        .is_some_and(|config| config.guardians.contains(&sender));
+    if is_guardian {
+        approvers.push(sender);
+    }
+    for (signer, signature) in signatures {
+        // Validate the signature.
+        validate_signature(
+            &message,
+            signer,
+            signature,
+            crypto_primitives,
+            ctx,
+            ctx.sender(),
+            ctx.named_entrypoint().as_entrypoint_name(),
+        )?;
+        approvers.push(Guardian::Key(signer));
+    }
+
+    let approvals = host.state_mut().approve_recovery(
+        &tag_key,
+        message.new_key,
+        message.nonce,
+        &approvers,
+        ctx.metadata().slot_time(),
+    )?;
+    logger.log(&Event::RecoveryApproved(RecoveryApprovedEvent {
+        tag,
+        new_key: message.new_key,
+        guardians: approvers,
+        approvals,
+    }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of a `CancelRecoveryParam`,
+/// which is what the key of the tag signs.
+#[receive(
+    contract = "registry",
+    name = "get_cancel_recovery_hash",
+    parameter = "CancelRecoveryParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_cancel_recovery_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: CancelRecoveryParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Cancels the pending recovery of a tag with a message signed by the current
+/// key of the tag. The recovery nonce of the tag is bumped, so the approvals of
+/// the recovery can not be replayed.
+/// Logs the `RecoveryCancelled` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or the signature is invalid.
+/// - the tag does not exist or is registered for a different key.
+/// - the nonce is wrong.
+/// - there is no pending recovery.
+#[receive(
+    contract = "registry",
+    name = "cancel_recovery",
+    parameter = "CancelRecoveryMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn cancel_recovery(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let CancelRecoveryMessage {
+        signer,
+        signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state_mut()
+        .use_nonce(&tag_key, signer, message.nonce)?;
+    let recovery = host.state_mut().cancel_recovery(&tag_key)?;
+    logger.log(&Event::RecoveryCancelled(RecoveryCancelledEvent {
+        tag,
+        new_key: recovery.new_key,
+    }))?;
+    Ok(())
+}
+
+/// Rebinds a tag to the new key of its pending recovery, once enough guardians
+/// approved it and the recovery window has passed. Anyone can finalize it.
+/// The new key takes over the guardians, while operators of the old key lapse.
+/// Logs the `Recovered` event, followed by the CIS-2 `TokenMetadata` event of
+/// the tag token.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - there is no pending recovery for the tag.
+/// - the recovery lacks approvals or its window has not passed yet.
+#[receive(
+    contract = "registry",
+    name = "finalize_recovery",
+    parameter = "String",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn finalize_recovery(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let tag: String = ctx.parameter_cursor().get()?;
+    let tag = canonical_tag(&tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    let (old_key, registry) = host
+        .state_mut()
+        .finalize_recovery(&tag_key, ctx.metadata().slot_time())?;
+    host.state_mut()
+        .update_merkle_leaf(&tag_key, crypto_primitives);
+
+    let token_id = tag_token_id(&tag);
+    let metadata_url = host.state().token_metadata_url(&tag);
+    logger.log(&Event::Recovered(RecoveredEvent {
+        tag,
+        old_key,
+        new_key: registry.public_key,
+    }))?;
+    logger.log(&Event::TokenMetadata(TokenMetadataEvent {
+        token_id,
+        metadata_url,
+    }))?;
+    Ok(())
+}
+
+/// Sets the delay that sensitive changes wait in the pending-change queue.
+/// Logs the `ChangeDelay` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+#[receive(
+    contract = "registry",
+    name = "set_change_delay",
+    parameter = "Duration",
+    error = "Error",
+    enable_logger,
+    mutable
+)]
+fn set_change_delay(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let delay: Duration = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
+
+    host.state_mut().change_delay = delay;
+    logger.log(&Event::ChangeDelay(ChangeDelayEvent { delay }))?;
+    Ok(())
+}
+
+/// Sets the anti-spam limits on registrations through `register`, `permit`
+/// and `reveal`: how many tags a provider and a sender may register per window,
+/// and how long a sender has to wait between two registrations. Auctions and
+/// imports are not limited.
+/// Logs the `Quotas` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+#[receive(
+    contract = "registry",
+    name = "set_quotas",
+    parameter = "Quotas",
+    error = "Error",
+    enable_logger,
+    mutable
+)]
+fn set_quotas(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let quotas: Quotas = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
+
+    host.state_mut().quotas = quotas.clone();
+    logger.log(&Event::Quotas(QuotasEvent { quotas }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of a `RotateKeyParam`, which
+/// is what both the current and the new key of the tag sign.
+#[receive(
+    contract = "registry",
+    name = "get_rotate_key_hash",
+    parameter = "RotateKeyParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_rotate_key_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: RotateKeyParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Queues moving a tag to a new key, with a message signed by both the current
+/// and the new key. The new key takes over the guardians once the change is
+/// executed, while operators of the old key lapse.
+/// Logs the `ChangeQueued` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or one of the signatures is invalid.
+/// - the tag does not exist or is registered for a different key.
+/// - the nonce is wrong.
+#[receive(
+    contract = "registry",
+    name = "rotate_key",
+    parameter = "RotateKeyMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn rotate_key(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let RotateKeyMessage {
+        signer,
+        signature,
+        new_key_signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signatures.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+    validate_signature(
+        &message,
+        message.new_key,
+        new_key_signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state_mut()
+        .use_nonce(&tag_key, signer, message.nonce)?;
+    let change = Change::PublicKey(message.new_key);
+    let change = host
+        .state_mut()
+        .queue_change(&tag_key, change, ctx.metadata().slot_time())?;
+    logger.log(&Event::ChangeQueued(ChangeQueuedEvent { tag, change }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of a `CancelPendingParam`,
+/// which is what the key of the tag signs.
+#[receive(
+    contract = "registry",
+    name = "get_cancel_pending_hash",
+    parameter = "CancelPendingParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_cancel_pending_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let param: CancelPendingParam = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&param), crypto_primitives, ctx)
+}
+
+/// Cancels a pending change of a tag with a message signed by the current key
+/// of the tag.
+/// Logs the `ChangeCancelled` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the message is expired or the signature is invalid.
+/// - the tag does not exist or is registered for a different key.
+/// - the nonce is wrong.
+/// - the tag has no such pending change.
+#[receive(
+    contract = "registry",
+    name = "cancel_pending",
+    parameter = "CancelPendingMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn cancel_pending(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let CancelPendingMessage {
+        signer,
+        signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state_mut()
+        .use_nonce(&tag_key, signer, message.nonce)?;
+    let pending = host.state_mut().take_change(&tag_key, message.id)?;
+    logger.log(&Event::ChangeCancelled(ChangeCancelledEvent {
+        tag,
+        id: pending.id,
+    }))?;
+    Ok(())
+}
+
+/// Executes a pending change of a tag once its delay has passed. Anyone can
+/// execute it.
+/// Logs the `ChangeExecuted` event, followed by the CIS-2 `TokenMetadata`
+/// event of the tag token.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the tag has no such pending change.
+/// - the delay of the change has not passed yet.
+/// - the change is a sale listing, which is executed by `buy`.
+#[receive(
+    contract = "registry",
+    name = "execute_pending",
+    parameter = "PendingChangeId",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn execute_pending(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: PendingChangeId = ctx.parameter_cursor().get()?;
+    let tag = canonical_tag(&param.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    let registry =
+        host.state_mut()
+            .execute_change(&tag_key, param.id, ctx.metadata().slot_time())?;
+    host.state_mut()
+        .update_merkle_leaf(&tag_key, crypto_primitives);
+
+    let token_id = tag_token_id(&tag);
+    let metadata_url = host.state().token_metadata_url(&tag);
+    logger.log(&Event::ChangeExecuted(ChangeExecutedEvent {
+        tag,
+        id: param.id,
+        registry,
+    }))?;
+    logger.log(&Event::TokenMetadata(TokenMetadataEvent {
+        token_id,
+        metadata_url,
+    }))?;
+    Ok(())
+}
+
+/// Gets the pending-change queue of a tag.
+/// The input parameter in this function is a `String`.
+#[receive(
+    contract = "registry",
+    name = "get_pending",
+    parameter = "String",
+    return_value = "Vec<PendingChange>",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_pending(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<Vec<PendingChange>> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state()
+        .pending_changes
+        .get(&tag_key(&canonical_tag(&tag), crypto_primitives))
+        .map(|queue| queue.clone())
+        .unwrap_or_default())
+}
+
+/// Imports registrations from an older registry instance, without the
+/// signatures of their keys. The entries are trusted to be validated already,
+/// e.g. replayed from the `Register` events of the older instance, see the
+/// `replay` command of the deploy scripts. Only the admin can import, and
+/// only until the import phase is sealed with `seal_import`.
+/// Logs the `Register` event of every entry, followed by the CIS-2 `Mint`
+/// event of the tag token to its holder and its `TokenMetadata` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+/// - the import phase is sealed.
+/// - one of the tags already exists.
+/// - one of the canonical tags or providers is longer than 128 bytes.
+#[receive(
+    contract = "registry",
+    name = "import",
+    parameter = "ImportParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn import(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: ImportParam = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
+    ensure!(!host.state().import_sealed, Error::ImportSealed.into());
+
+    for entry in param.entries {
+        let tag = canonical_tag(&entry.tag);
+        let tag_key = tag_key(&tag, crypto_primitives);
+        host.state_mut().insert(
+            tag_key,
+            RegistryEntry {
+                tag: tag.clone(),
+                registry: entry.data.clone(),
+                registrar: entry.registrar,
+                owner: entry.owner,
+                nonce: 0,
+                frozen: None,
+                registered_at: entry.registered_at,
+                updated_at: entry.registered_at,
+                position: 0,
+            },
+        )?;
+        host.state_mut()
+            .update_merkle_leaf(&tag_key, crypto_primitives);
+        let metadata_url = host.state().token_metadata_url(&tag);
+        log_registration(
+            logger,
+            tag.clone(),
+            entry.data,
+            entry.registrar,
+            entry.owner,
+            metadata_url,
+        )?;
+        deposit_escrow(host, logger, &tag, &tag_key)?;
+    }
+    Ok(())
+}
+
+/// Ends the import phase for good, after which `import` is no longer
+/// available.
+/// Logs the `ImportSealed` event.
+///
+/// It rejects if:
+/// - the sender is not the admin.
+/// - the import phase is already sealed.
+#[receive(
+    contract = "registry",
+    name = "seal_import",
+    error = "Error",
+    enable_logger,
+    mutable
+)]
+fn seal_import(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+) -> ReceiveResult<()> {
+    host.state().ensure_admin(ctx.sender())?;
+    ensure!(!host.state().import_sealed, Error::ImportSealed.into());
+
+    host.state_mut().import_sealed = true;
+    logger.log(&Event::ImportSealed(ImportSealedEvent {
+        total_tags: host.state().stats.total_tags,
+    }))?;
+    Ok(())
+}
+
+/// Gives an address the dispute role, or strips it of it. Moderators can
+/// freeze tags and resolve the dispute by unfreezing or reassigning them.
+/// Logs the `Moderator` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+#[receive(
+    contract = "registry",
+    name = "set_moderator",
+    parameter = "SetModeratorParam",
+    error = "Error",
+    enable_logger,
+    mutable
+)]
+fn set_moderator(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: SetModeratorParam = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
+
+    if param.enabled {
+        host.state_mut().moderators.insert(param.moderator);
+    } else {
+        host.state_mut().moderators.remove(&param.moderator);
+    }
+    logger.log(&Event::Moderator(ModeratorEvent {
+        moderator: param.moderator,
+        enabled: param.enabled,
+    }))?;
+    Ok(())
+}
+
+/// Freezes a tag for a dispute, such as a fraud or trademark claim. A frozen
+/// tag still resolves, with its dispute, but accepts no changes until it is
+/// unfrozen or reassigned. Freezing a frozen tag replaces its case reference.
+/// Logs the `Frozen` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender does not have the dispute role.
+/// - the tag does not exist.
+#[receive(
+    contract = "registry",
+    name = "freeze",
+    parameter = "FreezeParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn freeze(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: FreezeParam = ctx.parameter_cursor().get()?;
+    host.state().ensure_moderator(ctx.sender())?;
+
+    let tag = canonical_tag(&param.tag);
+    let freeze = Freeze {
+        case_reference: param.case_reference,
+        moderator: ctx.sender(),
+        frozen_at: ctx.metadata().slot_time(),
+    };
+    host.state_mut()
+        .freeze(&tag_key(&tag, crypto_primitives), freeze.clone())?;
+    logger.log(&Event::Frozen(FrozenEvent { tag, freeze }))?;
+    Ok(())
+}
+
+/// Unfreezes a tag, resolving its dispute in favour of the current holder.
+/// Logs the `Unfrozen` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender does not have the dispute role.
+/// - the tag does not exist or is not frozen.
+#[receive(
+    contract = "registry",
+    name = "unfreeze",
+    parameter = "String",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn unfreeze(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let tag: String = ctx.parameter_cursor().get()?;
+    host.state().ensure_moderator(ctx.sender())?;
+
+    let tag = canonical_tag(&tag);
+    host.state_mut()
+        .unfreeze(&tag_key(&tag, crypto_primitives))?;
+    logger.log(&Event::Unfrozen(UnfrozenEvent {
+        tag,
+        moderator: ctx.sender(),
+    }))?;
+    Ok(())
+}
+
+/// Reassigns a frozen tag to a new key and holder, resolving its dispute in
+/// favour of the claimant. The parameter holds the registration of the tag for
+/// the new key, signed by that key. The tag is unfrozen, and its pending
+/// changes and recoveries lapse along with the guardians and operators of the
+/// old key.
+/// Logs the `Reassigned` event, followed by the CIS-2 `Transfer` event of the
+/// tag token if its holder changed, and its `TokenMetadata` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender does not have the dispute role.
+/// - the registration is expired or wrongly signed.
+/// - the tag does not exist or is not frozen.
+/// - the provider of the new record is longer than 128 bytes.
+#[receive(
+    contract = "registry",
+    name = "reassign",
+    parameter = "ReassignParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn reassign(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let ReassignParam { message } = ctx.parameter_cursor().get()?;
+    host.state().ensure_moderator(ctx.sender())?;
+    let RegisterMessage {
+        signer,
+        signature,
+        message,
+    } = message;
+    ensure!(
+        signer == message.data.public_key,
+        Error::WrongSignature.into()
+    );
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    let entry = host.state().get(&tag_key)?;
+    ensure!(entry.frozen.is_some(), Error::TagNotFrozen.into());
+    let owner = message.owner;
+    let data = message.data;
+    host.state_mut()
+        .reassign(&tag_key, owner, data.clone(), ctx.metadata().slot_time())?;
+    host.state_mut()
+        .update_merkle_leaf(&tag_key, crypto_primitives);
+
+    let token_id = tag_token_id(&tag);
+    let metadata_url = host.state().token_metadata_url(&tag);
+    logger.log(&Event::Reassigned(ReassignedEvent {
+        tag,
+        registry: data,
+        owner,
+        moderator: ctx.sender(),
+    }))?;
+    if entry.owner != owner {
+        logger.log(&Event::Transfer(TransferEvent {
+            token_id: token_id.clone(),
+            amount: TokenAmountU8(1),
+            from: entry.owner,
+            to: owner,
+        }))?;
+    }
+    logger.log(&Event::TokenMetadata(TokenMetadataEvent {
+        token_id,
+        metadata_url,
+    }))?;
+    Ok(())
+}
+
+/// Gets the guardians of a tag, if any.
+/// The input parameter in this function is a `String`.
+#[receive(
+    contract = "registry",
+    name = "get_guardians",
+    parameter = "String",
+    return_value = "Option<GuardianConfig>",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_guardians(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<Option<GuardianConfig>> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state()
+        .guardians_of(&tag_key(&canonical_tag(&tag), crypto_primitives)))
+}
+
+/// Gets the pending recovery of a tag, if any.
+/// The input parameter in this function is a `String`.
+#[receive(
+    contract = "registry",
+    name = "get_recovery",
+    parameter = "String",
+    return_value = "Option<Recovery>",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_recovery(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<Option<Recovery>> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state()
+        .recoveries
+        .get(&tag_key(&canonical_tag(&tag), crypto_primitives))
+        .map(|recovery| recovery.clone()))
+}
+
+/// Gets the nonce that guardians sign in a `RecoveryParam` to approve a
+/// recovery of a tag.
+/// The input parameter in this function is a `String`.
+#[receive(
+    contract = "registry",
+    name = "get_recovery_nonce",
+    parameter = "String",
+    return_value = "u64",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_recovery_nonce(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<u64> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state()
+        .recovery_nonce(&tag_key(&canonical_tag(&tag), crypto_primitives)))
+}
+
+/// Gets the rights of an operator over a tag, if any.
+/// The input parameter in this function is a `TagOperatorQuery`.
+#[receive(
+    contract = "registry",
+    name = "get_tag_operator",
+    parameter = "TagOperatorQuery",
+    return_value = "Option<OperatorAuthorization>",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_tag_operator(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<Option<OperatorAuthorization>> {
+    let query: TagOperatorQuery = ctx.parameter_cursor().get()?;
+    let tag_key = tag_key(&canonical_tag(&query.tag), crypto_primitives);
+    Ok(host.state().tag_operator(&tag_key, query.operator))
+}
+
+/// Sets the protocol fee taken from every `buy`, in basis points of the price.
+/// Logs the `ProtocolFee` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+/// - the fee is above `10_000`, the whole price.
+#[receive(
+    contract = "registry",
+    name = "set_protocol_fee",
+    parameter = "u16",
+    error = "Error",
+    enable_logger,
+    mutable
+)]
+fn set_protocol_fee(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let fee: u16 = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
+    ensure!(fee <= MAX_PROTOCOL_FEE, Error::InvalidFee.into());
+
+    host.state_mut().protocol_fee = fee;
+    logger.log(&Event::ProtocolFee(ProtocolFeeEvent { fee }))?;
+    Ok(())
+}
+
+/// Sets the base of the metadata URLs of the tag tokens, e.g.
+/// `https://example.com/metadata/`. The metadata URL of a token is the base
+/// followed by its token id in hex, where the metadata generated with
+/// [token_metadata_json] is to be served.
+/// Logs the `MetadataUrl` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not the admin.
+/// - the URL is longer than `MAX_METADATA_URL_LENGTH` bytes.
+#[receive(
+    contract = "registry",
+    name = "set_metadata_url",
+    parameter = "String",
+    error = "Error",
+    enable_logger,
+    mutable
+)]
+fn set_metadata_url(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let url: String = ctx.parameter_cursor().get()?;
+    host.state().ensure_admin(ctx.sender())?;
+    ensure!(
+        url.len() <= MAX_METADATA_URL_LENGTH,
+        Error::MetadataUrlTooLong.into()
+    );
+
+    host.state_mut().metadata_url = url.clone();
+    logger.log(&Event::MetadataUrl(MetadataUrlEvent { url }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the hash of a `SaleOffer`, which is what the
+/// seller signs with its account keys, see `viewMessageHash`.
+#[receive(
+    contract = "registry",
+    name = "get_offer_hash",
+    parameter = "SaleOffer",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_offer_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let offer: SaleOffer = ctx.parameter_cursor().get()?;
+    Ok(calculate_account_message_hash(
+        &offer.seller,
+        &to_bytes(&offer),
+        crypto_primitives,
+    ))
+}
+
+/// Helper function to calculate the message hash of a `SaleOffer`, which is
+/// what the key of the tag signs to list it, and the hash of its listing in the
+/// pending-change queue.
+#[receive(
+    contract = "registry",
+    name = "get_offer_key_hash",
+    parameter = "SaleOffer",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_offer_key_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter.
+    let offer: SaleOffer = ctx.parameter_cursor().get()?;
+    calculate_message_hash_from_bytes(&to_bytes(&offer), crypto_primitives, ctx)
+}
+
+/// Lists a sale offer of a tag, with the offer signed by the key of the tag.
+/// Rebinding a tag to the key of a buyer is a sensitive change, so the listing
+/// waits in the pending-change queue of the tag, and the tag can only be
+/// bought with `buy` once the change delay has passed. During the delay the
+/// key can cancel the listing with `cancel_pending`.
+/// Logs the `ChangeQueued` event.
+///
+/// The listing uses the nonce of the tag, so the key can't sign other messages
+/// for the tag in the meantime without voiding the offer. A listing lapses
+/// with the other pending changes when the tag moves to another key.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the offer is expired or the signature is invalid.
+/// - the tag does not exist, is frozen or is registered for a different key.
+/// - the tag nonce is wrong.
+#[receive(
+    contract = "registry",
+    name = "list_sale",
+    parameter = "ListSaleMessage",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn list_sale(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let ListSaleMessage {
+        signer,
+        signature,
+        message,
+    } = ctx.parameter_cursor().get()?;
+    // Validate the signature.
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let tag = canonical_tag(&message.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    host.state_mut()
+        .use_nonce(&tag_key, signer, message.tag_nonce)?;
+    let offer_hash =
+        calculate_message_hash_from_bytes(&to_bytes(&message), crypto_primitives, ctx)?;
+    let change = host.state_mut().queue_change(
+        &tag_key,
+        Change::Sale(offer_hash),
+        ctx.metadata().slot_time(),
+    )?;
+    logger.log(&Event::ChangeQueued(ChangeQueuedEvent { tag, change }))?;
+    Ok(())
+}
+
+/// Buys a tag through a sale offer signed by its holder and listed by the key
+/// of the tag with `list_sale`, once the change delay of the listing has
+/// passed. The attached CCD must
+/// match the price, of which the protocol fee is paid to the admin and the
+/// rest to the seller. The parameter also holds the signed registration of the
+/// tag for the key of the buyer, which replaces the record of the tag, and its
+/// signed `owner` becomes the holder of the tag token.
+/// Logs the `Sale` event, followed by the CIS-2 `Transfer` and `TokenMetadata`
+/// events of the tag token and the CIS-3 `Nonce` event of the seller.
+///
+/// The offer uses the next `permit` nonce of the seller, so it is void once the
+/// seller executes another `permit` message or sale offer. The tag is rebound
+/// to the key of the buyer, the guardians of the old key stay behind.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not an account, or not the designated buyer.
+/// - the offer is expired, for a different contract or has a wrong nonce.
+/// - the seller does not exist or its signature is invalid.
+/// - the offer is not listed, or the delay of its listing has not passed yet.
+/// - the seller does not hold the tag, or the tag is frozen.
+/// - the attached amount is not the price.
+/// - the registration is for a different tag, expired or wrongly signed.
+/// - the provider of the new record is longer than 128 bytes.
+#[receive(
+    contract = "registry",
+    name = "buy",
+    parameter = "BuyParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    payable,
+    mutable
+)]
+fn buy(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: BuyParam = ctx.parameter_cursor().get()?;
+    let buyer = match ctx.sender() {
+        Address::Account(account) => account,
+        Address::Contract(_) => bail!(Error::OnlyAccount.into()),
+    };
+    let BuyParam {
+        signature,
+        offer,
+        message,
+    } = param;
+    let seller = offer.seller;
+
+    // Bump the nonce of the seller.
+    let mut entry = host.state_mut().nonces_registry.entry(seller).or_insert(0);
+    let nonce = *entry;
+    *entry += 1;
+    drop(entry);
+
+    ensure_eq!(offer.nonce, nonce, Error::NonceMismatch.into());
+    ensure_eq!(
+        offer.contract_address,
+        ctx.self_address(),
+        Error::WrongContract.into()
+    );
+    ensure!(
+        offer.expiry_time > ctx.metadata().slot_time(),
+        Error::Expired.into()
+    );
+    if let Some(designated) = offer.buyer {
+        ensure_eq!(designated, buyer, Error::UnAuthorized.into());
+    }
+    ensure_eq!(amount, offer.price, Error::WrongAmount.into());
+
+    // Check the signature of the seller.
+    let offer_hash = calculate_account_message_hash(&seller, &to_bytes(&offer), crypto_primitives);
+    let valid_signature = host
+        .check_account_signature(seller, &signature, &offer_hash)
+        .map_err(Error::from)?;
+    ensure!(valid_signature, Error::WrongSignature.into());
+
+    // Check that the key of the tag listed the offer long enough ago.
+    let tag = canonical_tag(&offer.tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    let offer_hash = calculate_message_hash_from_bytes(&to_bytes(&offer), crypto_primitives, ctx)?;
+    host.state_mut()
+        .take_listing(&tag_key, offer_hash, ctx.metadata().slot_time())?;
+
+    // Check the proof of key of the buyer.
+    let RegisterMessage {
+        signer,
+        signature,
+        message,
+    } = message;
+    ensure_eq!(canonical_tag(&message.tag), tag, Error::TagMismatch.into());
+    ensure!(
+        signer == message.data.public_key,
+        Error::WrongSignature.into()
+    );
+    validate_signature(
+        &message,
+        signer,
+        signature,
+        crypto_primitives,
+        ctx,
+        ctx.sender(),
+        ctx.named_entrypoint().as_entrypoint_name(),
+    )?;
+
+    let data = message.data;
+    let owner = message.owner;
+    host.state_mut().sell(
+        &tag_key,
+        Address::Account(seller),
+        owner,
+        data.clone(),
+        ctx.metadata().slot_time(),
+    )?;
+    host.state_mut()
+        .update_merkle_leaf(&tag_key, crypto_primitives);
+
+    // Pay out the seller and the protocol fee.
+    let fee = host.state().protocol_fee(amount);
+    if fee > Amount::zero() {
+        let admin = host.state().admin;
+        host.invoke_transfer(&admin, fee)?;
+    }
+    host.invoke_transfer(&seller, amount - fee)?;
+
+    let token_id = tag_token_id(&tag);
+    let metadata_url = host.state().token_metadata_url(&tag);
+    logger.log(&Event::Sale(SaleEvent {
+        tag,
+        seller,
+        buyer,
+        price: amount,
+        fee,
+        registry: data,
+    }))?;
+    logger.log(&Event::Transfer(TransferEvent {
+        token_id: token_id.clone(),
+        amount: TokenAmountU8(1),
+        from: Address::Account(seller),
+        to: owner,
+    }))?;
+    logger.log(&Event::TokenMetadata(TokenMetadataEvent {
+        token_id,
+        metadata_url,
+    }))?;
+    logger.log(&Event::Nonce(NonceEvent {
+        account: seller,
+        nonce,
+    }))?;
+    Ok(())
+}
+
+/// Sends the attached CCD to a tag. The tag is resolved to its record, and the
+/// CCD is deposited through the CIS-5 `depositCcd` entry point of its
+/// `contract_address`, credited to its `public_key`.
+/// The input parameter in this function is a `String`.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the tag does not exist or is frozen.
+/// - the key of the tag is not an Ed25519 key, which CIS-5 wallets hold CCD
+///   for.
+/// - the deposit into the wallet fails.
+#[receive(
+    contract = "registry",
+    name = "transfer_to_tag",
+    parameter = "String",
+    error = "Error",
+    crypto_primitives,
+    payable,
+    mutable
+)]
+fn transfer_to_tag(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let tag: String = ctx.parameter_cursor().get()?;
+    let (wallet, key) = host
+        .state()
+        .wallet(&tag_key(&canonical_tag(&tag), crypto_primitives))?;
+    host.invoke_contract(&wallet, &key, DEPOSIT_CCD_ENTRYPOINT, amount)
+        .map_err(Error::from)?;
+    Ok(())
+}
+
+/// Receives CIS-2 tokens sent to a tag, as defined by CIS-2. The `data` of the
+/// transfer is the tag, serialized as a `String`. The tokens are forwarded
+/// with a CIS-2 `transfer` of the sending token contract to the CIS-5
+/// `depositCis2Tokens` entry point of the `contract_address` of the tag,
+/// credited to its `public_key`.
+///
+/// It rejects if:
+/// - it fails to parse the parameter, e.g. the `data` is not a tag.
+/// - the sender is not a contract.
+/// - the tag does not exist or is frozen.
+/// - the key of the tag is not an Ed25519 key, which CIS-5 wallets hold tokens
+///   for.
+/// - forwarding the tokens fails.
+#[receive(
+    contract = "registry",
+    name = "onReceivingCIS2",
+    parameter = "OnReceivingTagParams",
+    error = "Error",
+    crypto_primitives,
+    mutable
+)]
+fn on_receiving_cis2(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: OnReceivingTagParams = ctx.parameter_cursor().get()?;
+    let Address::Contract(token_contract) = ctx.sender() else {
+        bail!(Error::UnAuthorized.into());
+    };
+    let (wallet, key) = host
+        .state()
+        .wallet(&tag_key(&canonical_tag(&param.data), crypto_primitives))?;
+    let transfer = Transfer {
+        token_id: param.token_id,
+        amount: param.amount,
+        from: Address::Contract(ctx.self_address()),
+        to: Receiver::Contract(
+            wallet,
+            OwnedEntrypointName::new_unchecked(DEPOSIT_CIS2_TOKENS_ENTRYPOINT.into()),
+        ),
+        data: AdditionalData::from(to_bytes(&key)),
+    };
+    host.invoke_contract(
+        &token_contract,
+        &TokenTransferParams::from(vec![transfer]),
+        TRANSFER_ENTRYPOINT,
+        Amount::zero(),
+    )
+    .map_err(Error::from)?;
+    Ok(())
+}
+
+/// Pays the attached CCD to a tag that is not registered yet, e.g. to invite a
+/// new user. The CCD is held in escrow and deposited into the wallet of the
+/// tag once it is registered, like with `transfer_to_tag`. A wallet rejecting
+/// the deposit does not hold up the registration, the CCD then stays in escrow
+/// until `release_escrow` deposits it. If the CCD is not deposited within
+/// `ESCROW_TIMEOUT`, the sender can take it back with `refund_escrow`.
+/// The input parameter in this function is a `String`.
+/// Logs the `Escrowed` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not an account.
+/// - no CCD is attached.
+/// - the tag is already registered, use `transfer_to_tag` instead.
+#[receive(
+    contract = "registry",
+    name = "escrow",
+    parameter = "String",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    payable,
+    mutable
+)]
+fn escrow(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let tag: String = ctx.parameter_cursor().get()?;
+    let sender = match ctx.sender() {
+        Address::Account(account) => account,
+        Address::Contract(_) => bail!(Error::OnlyAccount.into()),
+    };
+    ensure!(amount > Amount::zero(), Error::WrongAmount.into());
+    let tag = canonical_tag(&tag);
+    let refundable_at = ctx
+        .metadata()
+        .slot_time()
+        .checked_add(ESCROW_TIMEOUT)
+        .ok_or(Error::Overflow)?;
+    host.state_mut().escrow(
+        tag_key(&tag, crypto_primitives),
+        Escrow {
+            sender,
+            amount,
+            refundable_at,
+        },
+    )?;
+    logger.log(&Event::Escrowed(EscrowedEvent {
+        tag,
+        sender,
+        amount,
+        refundable_at,
+    }))?;
+    Ok(())
+}
+
+/// Deposits the escrow of a registered tag into its wallet, for when the wallet
+/// rejected the deposit at the registration, see `escrow`.
+/// The input parameter in this function is a `String`.
+/// Logs the `EscrowReleased` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the tag has no escrow.
+/// - the tag is not registered, is frozen or its key can't hold CCD in a CIS-5
+///   wallet.
+/// - the wallet rejects the deposit again.
+#[receive(
+    contract = "registry",
+    name = "release_escrow",
+    parameter = "String",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn release_escrow(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let tag: String = ctx.parameter_cursor().get()?;
+    let tag = canonical_tag(&tag);
+    let tag_key = tag_key(&tag, crypto_primitives);
+    ensure!(
+        host.state().escrows.get(&tag_key).is_some(),
+        Error::EscrowDoesNotExist.into()
+    );
+    host.state().wallet(&tag_key)?;
+    ensure!(
+        deposit_escrow(host, logger, &tag, &tag_key)?,
+        Error::InvokeContractError.into()
+    );
+    Ok(())
+}
+
+/// Takes back the CCD the sender paid to a tag with `escrow`, once its escrow
+/// timed out without being deposited into the wallet of the tag.
+/// The input parameter in this function is a `String`.
+/// Logs the `EscrowRefunded` event.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - the sender is not an account.
+/// - the sender has no escrow for the tag.
+/// - none of the escrow of the sender has timed out yet.
+#[receive(
+    contract = "registry",
+    name = "refund_escrow",
+    parameter = "String",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn refund_escrow(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let tag: String = ctx.parameter_cursor().get()?;
+    let sender = match ctx.sender() {
+        Address::Account(account) => account,
+        Address::Contract(_) => bail!(Error::OnlyAccount.into()),
+    };
+    let tag = canonical_tag(&tag);
+    let amount = host.state_mut().refund_escrow(
+        &tag_key(&tag, crypto_primitives),
+        sender,
+        ctx.metadata().slot_time(),
+    )?;
+    host.invoke_transfer(&sender, amount)?;
+    logger.log(&Event::EscrowRefunded(EscrowRefundedEvent {
+        tag,
+        sender,
+        amount,
+    }))?;
+    Ok(())
+}
+
+/// Helper function to calculate the message hash of a `PermitParam`, which is
+/// what the signer of a `permit` message signs.
+///
+/// The message is prepended with the `signer` account address and 8 zero
+/// bytes, the same way the Concordium wallets prepend signed messages. The
+/// zero bytes ensure that a message can never be mistaken for a transaction.
+#[receive(
+    contract = "registry",
+    name = "viewMessageHash",
+    parameter = "PermitParam",
+    return_value = "[u8;32]",
+    error = "Error",
+    crypto_primitives,
+    mutable
+)]
+fn contract_view_message_hash(
+    ctx: &ReceiveContext,
+    _host: &mut Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<[u8; 32]> {
+    // Parse the parameter, only the `signature` and the `signer` are read here.
+    let mut cursor = ctx.parameter_cursor();
+    let param: PermitParamPartial = cursor.get()?;
+
+    // The remaining bytes are the serialized `message`.
+    let mut message_bytes = vec![0; (cursor.size() - cursor.cursor_position()) as usize];
+    cursor.read_exact(&mut message_bytes)?;
+
+    Ok(calculate_account_message_hash(
+        &param.signer,
+        &message_bytes,
+        crypto_primitives,
+    ))
+}
+
+/// Calculates the hash that `signer` signs with its account keys for the
+/// message bytes. The message is prepended with the `signer` account address
+/// and 8 zero bytes, the same way the Concordium wallets prepend signed
+/// messages.
+fn calculate_account_message_hash(
+    signer: &AccountAddress,
+    message_bytes: &[u8],
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> [u8; 32] {
+    let mut msg_prepend = [0; 32 + 8];
+    msg_prepend[0..32].copy_from_slice(signer.as_ref());
+
+    // Calculate the message hash.
+    crypto_primitives
+        .hash_sha2_256(&[&msg_prepend[0..40], message_bytes].concat())
+        .0
+}
+
+/// Executes a message signed by an account on behalf of that account, as
+/// defined by CIS-3. The transaction can be submitted by anyone, so users that
+/// hold no CCD can have a sponsor pay for it.
+/// Logs the `Nonce` event along with the events of the invoked entry point.
+///
+/// The signer is treated as the sender of the invoked entry point, so e.g. a
+/// permitted `register` records the signer as the registrar. Only the entry
+/// points that act for their sender are supported: `register`, `commit`,
+/// `reveal`, `transfer` and `updateOperator`, see `supportsPermit`. Messages
+/// signed by the key of a tag can already be sent by anyone, payable entry
+/// points need CCD that a `permit` message can not attach, and the admin and
+/// moderators are expected to pay for their own transactions.
+///
+/// It rejects if:
+/// - it fails to parse the parameter or the payload.
+/// - the nonce is wrong.
+/// - the message was intended for a different contract.
+/// - the message is expired.
+/// - the signer account does not exist or the signature is invalid.
+/// - the entry point does not support `permit`.
+#[receive(
+    contract = "registry",
+    name = "permit",
+    parameter = "PermitParam",
+    error = "Error",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn permit(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ReceiveResult<()> {
+    // Parse the parameter.
+    let param: PermitParam = ctx.parameter_cursor().get()?;
+
+    // Bump the nonce of the signer.
+    let mut entry = host
+        .state_mut()
+        .nonces_registry
+        .entry(param.signer)
+        .or_insert(0);
+    let nonce = *entry;
+    *entry += 1;
+    drop(entry);
+
+    let message = param.message;
+
+    // Check the nonce to prevent replay attacks.
+    ensure_eq!(message.nonce, nonce, Error::NonceMismatch.into());
+
+    // Check that the message was intended for this contract.
+    ensure_eq!(
+        message.contract_address,
+        ctx.self_address(),
+        Error::WrongContract.into()
+    );
+
+    // Check that the message is not expired.
+    ensure!(
+        message.timestamp > ctx.metadata().slot_time(),
+        Error::Expired.into()
+    );
+
+    // Check the signature.
+    let message_hash = contract_view_message_hash(ctx, host, crypto_primitives)?;
+    let valid_signature = host
+        .check_account_signature(param.signer, &param.signature, &message_hash)
+        .map_err(Error::from)?;
+    ensure!(valid_signature, Error::WrongSignature.into());
+
+    let signer = Address::Account(param.signer);
+    match message.entry_point.as_entrypoint_name() {
+        REGISTER_ENTRYPOINT => {
+            let param: RegisterMessage = from_bytes(&message.payload)?;
+            register_tag(
+                ctx,
+                host,
+                logger,
+                crypto_primitives,
+                param,
+                signer,
+                REGISTER_ENTRYPOINT,
+            )?;
+        }
+        COMMIT_ENTRYPOINT => {
+            let commitment: [u8; 32] = from_bytes(&message.payload)?;
+            commit_tag(ctx, host, logger, commitment, signer)?;
+        }
+        REVEAL_ENTRYPOINT => {
+            let param: RevealParam = from_bytes(&message.payload)?;
+            reveal_tag(ctx, host, logger, crypto_primitives, param, signer)?;
+        }
+        TRANSFER_ENTRYPOINT => {
+            let TransferParams(transfers): TransferParameter = from_bytes(&message.payload)?;
+            transfer_tags(ctx, host, logger, crypto_primitives, transfers, signer)?;
+        }
+        UPDATE_OPERATOR_ENTRYPOINT => {
+            let UpdateOperatorParams(updates) = from_bytes(&message.payload)?;
+            update_operators(host, logger, updates, signer)?;
+        }
+        _ => bail!(Error::UnsupportedPermitEntryPoint.into()),
+    }
+
+    logger.log(&Event::Nonce(NonceEvent {
+        account: param.signer,
+        nonce,
+    }))?;
+
+    Ok(())
+}
+
+/// Gets the next nonce that each of the given accounts has to use in a
+/// `permit` message.
+#[receive(
+    contract = "registry",
+    name = "nonceOf",
+    parameter = "VecOfAccountAddresses",
+    return_value = "NonceOfQueryResponse",
+    error = "Error"
+)]
+fn nonce_of(ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<NonceOfQueryResponse> {
+    let params: VecOfAccountAddresses = ctx.parameter_cursor().get()?;
+    let nonces = params
+        .queries
+        .iter()
+        .map(|account| host.state.nonces_registry.get(account).map_or(0, |n| *n))
+        .collect();
+    Ok(NonceOfQueryResponse(nonces))
+}
+
+/// Reports for each of the given entry points whether it can be invoked
+/// through `permit`.
+#[receive(
+    contract = "registry",
+    name = "supportsPermit",
+    parameter = "SupportsPermitQueryParams",
+    return_value = "SupportsQueryResponse",
+    error = "Error"
+)]
+fn supports_permit(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+) -> RegistryResult<SupportsQueryResponse> {
+    let params: SupportsPermitQueryParams = ctx.parameter_cursor().get()?;
+    let response: Vec<SupportResult> = params
+        .queries
+        .iter()
+        .map(|entrypoint| {
+            if SUPPORTS_PERMIT_ENTRYPOINTS.contains(&entrypoint.as_entrypoint_name()) {
+                SupportResult::Support
+            } else {
+                SupportResult::NoSupport
+            }
+        })
+        .collect();
+    Ok(SupportsQueryResponse::from(response))
+}
+
+/// Reports for each of the given standards whether this contract supports it,
+/// as defined by CIS-0.
+#[receive(
+    contract = "registry",
+    name = "supports",
+    parameter = "SupportsQueryParams",
+    return_value = "SupportsQueryResponse",
+    error = "Error"
+)]
+fn supports(ctx: &ReceiveContext, _host: &Host<State>) -> RegistryResult<SupportsQueryResponse> {
+    let params: SupportsQueryParams = ctx.parameter_cursor().get()?;
+    let response: Vec<SupportResult> = params
+        .queries
+        .iter()
+        .map(|std_id| {
+            if SUPPORTS_STANDARDS.contains(&std_id.as_standard_identifier()) {
+                SupportResult::Support
+            } else {
+                SupportResult::NoSupport
+            }
+        })
+        .collect();
+    Ok(SupportsQueryResponse::from(response))
+}
+
+/// Transfers tag tokens, as defined by CIS-2. A tag can't change hands
+/// without the key it resolves to, so the `data` of every transfer of a
+/// non-zero amount starts with a `RegisterMessage` of the tag, signed for
+/// `transfer` by the key of the receiver, whose `owner` is the receiver. The
+/// rest of the `data` is passed on to a receiving contract. The tag is rebound
+/// to that key and its record replaced, as in a sale, and the guardians of the
+/// old key stay behind. Transferring `0` only checks that the token exists, as
+/// required by CIS-2.
+/// Logs the CIS-2 `Transfer` event, followed by the `Update` event and the
+/// CIS-2 `TokenMetadata` event, for every transfer of a non-zero amount.
+///
+/// It rejects if:
+/// - it fails to parse the parameter or the registration in `data`.
+/// - a token id is not a registered tag.
+/// - the sender is neither the owner nor an operator of the owner.
+/// - the owner does not hold the amount, or the tag is frozen.
+/// - the registration is for a different tag or owner, expired or wrongly
+///   signed.
+/// - the provider of the new record is longer than 128 bytes.
+/// - the receive hook of a receiving contract fails.
+#[receive(
+    contract = "registry",
+    name = "transfer",
+    parameter = "TransferParameter",
+    error = "ContractError",
+    crypto_primitives,
+    enable_logger,
+    mutable
+)]
+fn transfer(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ContractResult<()> {
+    // Parse the parameter.
+    let TransferParams(transfers): TransferParameter = ctx.parameter_cursor().get()?;
+    transfer_tags(
+        ctx,
+        host,
+        logger,
+        crypto_primitives,
+        transfers,
+        ctx.sender(),
+    )
+}
+
+/// Executes CIS-2 transfers on behalf of `sender`.
+/// Shared by the `transfer` and `permit` entry points.
+fn transfer_tags(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+    transfers: Vec<Transfer<ContractTokenId, ContractTokenAmount>>,
+    sender: Address,
+) -> ContractResult<()> {
+    for Transfer {
+        token_id,
+        amount,
+        from,
+        to,
+        data,
+    } in transfers
+    {
+        ensure!(
+            from == sender || host.state().is_operator(&sender, &from),
+            ContractError::Unauthorized
+        );
+        let tag = token_tag(&token_id)?;
+        let tag_key = tag_key(&tag, crypto_primitives);
+        let to_address = to.address();
+        let data = if amount == TokenAmountU8(0) {
+            host.state().balance(&tag_key, &from)?;
+            data
+        } else {
+            // Check the proof of key of the receiver, the rest of the data is
+            // passed on to a receiving contract.
+            let mut cursor = Cursor::new(data.as_ref());
+            let RegisterMessage {
+                signer,
+                signature,
+                message,
+            } = cursor.get()?;
+            let data = AdditionalData::from(data.as_ref()[cursor.offset..].to_vec());
+            ensure_eq!(
+                canonical_tag(&message.tag),
+                tag,
+                ContractError::Custom(Error::TagMismatch)
+            );
+            ensure_eq!(
+                message.owner,
+                to_address,
+                ContractError::Custom(Error::OwnerMismatch)
+            );
+            ensure!(
+                signer == message.data.public_key,
+                ContractError::Custom(Error::WrongSignature)
+            );
+            validate_signature(
+                &message,
+                signer,
+                signature,
+                crypto_primitives,
+                ctx,
+                sender,
+                TRANSFER_ENTRYPOINT,
+            )
+            .map_err(ContractError::Custom)?;
+
+            let registry = message.data;
+            host.state_mut().transfer(
+                &tag_key,
+                amount,
+                &from,
+                &to_address,
+                registry.clone(),
+                ctx.metadata().slot_time(),
+            )?;
+            host.state_mut()
+                .update_merkle_leaf(&tag_key, crypto_primitives);
+            let metadata_url = host.state().token_metadata_url(&tag);
+            logger.log(&Event::Transfer(TransferEvent {
+                token_id: token_id.clone(),
+                amount,
+                from,
+                to: to_address,
+            }))?;
+            logger.log(&Event::Update(UpdateEvent {
+                tag,
+                registry,
+                updated_by: sender,
+            }))?;
+            logger.log(&Event::TokenMetadata(TokenMetadataEvent {
+                token_id: token_id.clone(),
+                metadata_url,
+            }))?;
+            data
+        };
+
+        // Notify a receiving contract.
+        if let Receiver::Contract(address, function) = to {
+            let parameter = OnReceivingCis2Params {
+                token_id,
+                amount,
+                from,
+                data,
+            };
+            host.invoke_contract(
+                &address,
+                &parameter,
+                function.as_entrypoint_name(),
+                Amount::zero(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds or removes operators of the sender, as defined by CIS-2. An operator
+/// can transfer every tag token of the sender.
+/// Logs an `UpdateOperator` event for every update.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+#[receive(
+    contract = "registry",
+    name = "updateOperator",
+    parameter = "UpdateOperatorParams",
+    error = "ContractError",
+    enable_logger,
+    mutable
+)]
+fn update_operator(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut Logger,
+) -> ContractResult<()> {
+    // Parse the parameter.
+    let UpdateOperatorParams(updates) = ctx.parameter_cursor().get()?;
+    update_operators(host, logger, updates, ctx.sender())
+}
+
+/// Applies CIS-2 operator updates on behalf of `owner`.
+/// Shared by the `updateOperator` and `permit` entry points.
+fn update_operators(
+    host: &mut Host<State>,
+    logger: &mut Logger,
+    updates: Vec<UpdateOperator>,
+    owner: Address,
+) -> ContractResult<()> {
+    for UpdateOperator { update, operator } in updates {
+        host.state_mut().update_operator(update, owner, operator);
+        logger.log(&Event::UpdateOperator(UpdateOperatorEvent {
+            update,
+            owner,
+            operator,
+        }))?;
+    }
+    Ok(())
+}
+
+/// Gets the balances of tag tokens, as defined by CIS-2. The balance is `1`
+/// for the owner of a tag and `0` for everyone else.
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - a token id is not a registered tag.
+#[receive(
+    contract = "registry",
+    name = "balanceOf",
+    parameter = "ContractBalanceOfQueryParams",
+    return_value = "ContractBalanceOfQueryResponse",
+    error = "ContractError",
+    crypto_primitives
+)]
+fn balance_of(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ContractResult<ContractBalanceOfQueryResponse> {
+    let params: ContractBalanceOfQueryParams = ctx.parameter_cursor().get()?;
+    let mut response = Vec::with_capacity(params.queries.len());
+    for query in params.queries {
+        let tag = token_tag(&query.token_id)?;
+        let tag_key = tag_key(&tag, crypto_primitives);
+        response.push(host.state().balance(&tag_key, &query.address)?);
+    }
+    Ok(BalanceOfQueryResponse::from(response))
+}
+
+/// Reports whether the given addresses are operators of the given owners, as
+/// defined by CIS-2.
+#[receive(
+    contract = "registry",
+    name = "operatorOf",
+    parameter = "OperatorOfQueryParams",
+    return_value = "OperatorOfQueryResponse",
+    error = "ContractError"
+)]
+fn operator_of(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+) -> ContractResult<OperatorOfQueryResponse> {
+    let params: OperatorOfQueryParams = ctx.parameter_cursor().get()?;
+    let response: Vec<bool> = params
+        .queries
+        .iter()
+        .map(|query| host.state().is_operator(&query.address, &query.owner))
+        .collect();
+    Ok(OperatorOfQueryResponse::from(response))
+}
+
+/// Gets the metadata URLs of tag tokens, as defined by CIS-2. The metadata is
+/// served under the URL set with `set_metadata_url`, generated from the
+/// current record of the tag, see [token_metadata_url].
+///
+/// It rejects if:
+/// - it fails to parse the parameter.
+/// - a token id is not a registered tag.
+#[receive(
+    contract = "registry",
+    name = "tokenMetadata",
+    parameter = "ContractTokenMetadataQueryParams",
+    return_value = "TokenMetadataQueryResponse",
+    error = "ContractError",
+    crypto_primitives
+)]
+fn token_metadata(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> ContractResult<TokenMetadataQueryResponse> {
+    let params: ContractTokenMetadataQueryParams = ctx.parameter_cursor().get()?;
+    let mut response = Vec::with_capacity(params.queries.len());
+    for token_id in params.queries {
+        let tag = token_tag(&token_id)?;
+        ensure!(
+            host.state()
+                .registry
+                .get(&tag_key(&tag, crypto_primitives))
+                .is_some(),
+            ContractError::InvalidTokenId
+        );
+        response.push(host.state().token_metadata_url(&tag));
+    }
+    Ok(TokenMetadataQueryResponse::from(response))
+}
+
+/// Get's the registered record of a tag.
+/// The input parameter in this function is a `String`.
+/// The record is returned with the registrar and the slot times at which the
+/// tag was registered and last updated.
+#[receive(
+    contract = "registry",
+    name = "get_key",
+    parameter = "String",
+    return_value = "RegistryEntry",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_key(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<RegistryEntry> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    host.state
+        .get(&tag_key(&canonical_tag(&tag), crypto_primitives))
+}
+
+/// Resolves a tag to its record and metadata.
+/// The input parameter in this function is a `String`. Unlike `get_key` this
+/// view does not reject for an unknown tag but returns `None`, so that callers
+/// can tell a miss from a failure.
+#[receive(
+    contract = "registry",
+    name = "resolve",
+    parameter = "String",
+    return_value = "Option<TagRecord>",
+    error = "Error",
+    crypto_primitives
+)]
+fn resolve(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<Option<TagRecord>> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state
+        .resolve(&tag_key(&canonical_tag(&tag), crypto_primitives)))
+}
+
+/// Returns the CCD held in escrow for a tag that is not registered yet, or
+/// whose wallet rejected the deposit, see `escrow`.
+/// The input parameter in this function is a `String`.
+#[receive(
+    contract = "registry",
+    name = "get_escrows",
+    parameter = "String",
+    return_value = "Vec<Escrow>",
+    error = "Error",
+    crypto_primitives
+)]
+fn get_escrows(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<Vec<Escrow>> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state
+        .escrows
+        .get(&tag_key(&canonical_tag(&tag), crypto_primitives))
+        .map(|escrows| escrows.clone())
+        .unwrap_or_default())
+}
+
+/// Returns the anti-spam limits on registrations, see `set_quotas`.
+#[receive(
+    contract = "registry",
+    name = "get_quotas",
+    return_value = "Quotas",
+    error = "Error"
+)]
+fn get_quotas(_ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<Quotas> {
+    Ok(host.state.quotas.clone())
+}
+
+/// Lists the registered tags a page at a time.
+/// The input parameter in this function is a `ListTagsParam`; the page size is
+/// capped at `MAX_PAGE_SIZE`. The tags are listed in the order they were
+/// registered. Pass the returned `next_cursor`, the last tag of the page, to
+/// fetch the following page, it is `None` once the registry has been
+/// exhausted.
+#[receive(
+    contract = "registry",
+    name = "list_tags",
+    parameter = "ListTagsParam",
+    return_value = "ListTagsResponse",
+    error = "Error",
+    crypto_primitives
+)]
+fn list_tags(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> RegistryResult<ListTagsResponse> {
+    let param: ListTagsParam = ctx.parameter_cursor().get()?;
+    let cursor = param
+        .cursor
+        .map(|cursor| tag_key(&canonical_tag(&cursor), crypto_primitives));
+    let (tags, next_cursor) = host.state.list(cursor, param.limit)?;
+    Ok(ListTagsResponse { tags, next_cursor })
+}
+
+/// Returns the registry statistics.
+/// The input parameter in this function is a `StatsParam` naming the providers
+/// and namespaces whose tag counts should be included in the response.
+#[receive(
+    contract = "registry",
+    name = "stats",
+    parameter = "StatsParam",
+    return_value = "StatsResponse",
+    error = "Error"
+)]
+fn stats(ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<StatsResponse> {
+    let param: StatsParam = ctx.parameter_cursor().get()?;
+    Ok(host.state.stats(param.providers, param.namespaces))
+}
+
+/// Returns the root of the Merkle tree over all tags and their records. Light
+/// clients can check a resolution against it with a proof, see [merkle].
+#[receive(
+    contract = "registry",
+    name = "merkle_root",
+    return_value = "[u8;32]",
+    error = "Error"
+)]
+fn merkle_root(_ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<merkle::Hash> {
+    Ok(host
+        .state
+        .merkle_top
+        .as_ref()
+        .map_or(merkle::EMPTY, |top| top.hash))
+}
+
+/// Get's the registered tag for a user. A key with several tags returns its
+/// primary tag, see `set_primary`.
+/// The input parameter in this function is a `RegistryKey`.
+#[receive(
+    contract = "registry",
+    name = "get_tag",
+    parameter = "RegistryKey",
+    error = "Error"
+)]
+fn get_tag(ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<String> {
+    let key: RegistryKey = ctx.parameter_cursor().get()?;
+    host.state.get_tag(key)
+}
+
+/// Get's all tags registered for a key, together with its primary tag.
+/// The input parameter in this function is a `RegistryKey`.
+#[receive(
+    contract = "registry",
+    name = "get_tags",
+    parameter = "RegistryKey",
+    return_value = "KeyTags",
+    error = "Error"
+)]
+fn get_tags(ctx: &ReceiveContext, host: &Host<State>) -> RegistryResult<KeyTags> {
+    let key: RegistryKey = ctx.parameter_cursor().get()?;
+    host.state.get_tags(key)
 }
@@ -0,0 +1,427 @@
+//! A Merkle commitment of the registry, for light clients.
+//!
+//! The registry keeps the root of a Merkle tree over all pairs of a canonical
+//! tag and its [Registry] record, see the `merkle_root` view. The leaf of a tag
+//! sits at the path given by the bits of `sha256(tag)`, so every tag has
+//! exactly one place in the tree, and a proof can show that a tag is
+//! registered with a given record, or that it is not registered at all.
+//!
+//! The tree is compressed, so that a registration only adds a single node to
+//! the contract state:
+//! - a subtree without tags is [EMPTY].
+//! - a subtree with a single tag is the leaf of that tag, that is
+//!   `sha256(0x00 || tag || record)`, with the tag and the record serialized as
+//!   in the contract.
+//! - any other subtree is the node at the first bit its tags differ in, that is
+//!   `sha256(0x01 || depth || left || right)`, with the depth of that bit as a
+//!   big-endian `u16`.
+//!
+//! With the `std` feature, [MerkleTree] builds the tree and its proofs from an
+//! off-chain copy of the registry, e.g. read with `list_tags`, and
+//! [crate::sha256] can be passed as the hash function to verify them.
+#[cfg(feature = "std")]
+use crate::sha256;
+use crate::types::Registry;
+use concordium_std::*;
+
+/// A node of the tree.
+pub type Hash = [u8; 32];
+
+/// The depth of the tree, that is the number of bits of a path.
+pub const DEPTH: usize = 256;
+
+/// The hash of a subtree without any registered tag.
+pub const EMPTY: Hash = [0u8; 32];
+
+/// Returns the path of a canonical tag in the tree.
+pub fn leaf_key(tag: &str, hash: impl Fn(&[u8]) -> Hash) -> Hash {
+    hash(tag.as_bytes())
+}
+
+/// Returns the leaf of a canonical tag registered with `registry`.
+pub fn leaf_hash(tag: &str, registry: &Registry, hash: impl Fn(&[u8]) -> Hash) -> Hash {
+    let mut bytes = vec![0u8];
+    bytes.extend(to_bytes(&String::from(tag)));
+    bytes.extend(to_bytes(registry));
+    hash(&bytes)
+}
+
+/// Returns the node at `depth` above `left` and `right`.
+pub fn node_hash(depth: u16, left: &Hash, right: &Hash, hash: impl Fn(&[u8]) -> Hash) -> Hash {
+    let mut bytes = [0u8; 67];
+    bytes[0] = 1;
+    bytes[1..3].copy_from_slice(&depth.to_be_bytes());
+    bytes[3..35].copy_from_slice(left);
+    bytes[35..].copy_from_slice(right);
+    hash(&bytes)
+}
+
+/// Returns the `i`-th bit of `key`, counting from the most significant bit.
+/// Set bits lead to the right.
+pub fn bit(key: &Hash, i: usize) -> bool {
+    key[i / 8] & (0x80 >> (i % 8)) != 0
+}
+
+/// Returns the first `depth` bits of `key`, followed by zeros.
+pub fn prefix(key: &Hash, depth: usize) -> Hash {
+    let mut prefix = EMPTY;
+    prefix[..depth / 8].copy_from_slice(&key[..depth / 8]);
+    let bits = depth % 8;
+    if bits > 0 {
+        prefix[depth / 8] = key[depth / 8] & !(0xff >> bits);
+    }
+    prefix
+}
+
+/// Returns the first bit `a` and `b` differ in, if any.
+pub fn first_difference(a: &Hash, b: &Hash) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(a, b)| a != b)
+        .map(|i| i * 8 + (a[i] ^ b[i]).leading_zeros() as usize)
+}
+
+/// A subtree below a [MerkleNode], as kept in the contract state.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub struct MerkleChild {
+    pub hash: Hash,
+    /// The path of one of the tags in the subtree, to find where the path of a
+    /// new tag branches off.
+    pub key: Hash,
+    /// The depth of the node at the top of the subtree, `None` if the subtree
+    /// is a leaf. This lets an update walk down the path of a tag node by
+    /// node, rather than probe every depth.
+    pub depth: Option<u16>,
+}
+
+/// A node of the tree, as kept in the contract state under its depth and the
+/// prefix of its tags.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub struct MerkleNode {
+    pub left: MerkleChild,
+    pub right: MerkleChild,
+}
+
+impl MerkleNode {
+    /// Returns the node at `depth` above the subtrees `a` and `b`, which
+    /// differ in that bit.
+    pub fn new(depth: usize, a: MerkleChild, b: MerkleChild) -> Self {
+        if bit(&a.key, depth) {
+            Self { left: b, right: a }
+        } else {
+            Self { left: a, right: b }
+        }
+    }
+
+    /// Returns the hash of the node at `depth`.
+    pub fn hash(&self, depth: u16, hash: impl Fn(&[u8]) -> Hash) -> Hash {
+        node_hash(depth, &self.left.hash, &self.right.hash, hash)
+    }
+
+    /// Returns the subtree on the `right` or left.
+    pub fn child(&self, right: bool) -> &MerkleChild {
+        if right {
+            &self.right
+        } else {
+            &self.left
+        }
+    }
+
+    /// Replaces the subtree on the `right` or left.
+    pub fn set_child(&mut self, right: bool, child: MerkleChild) {
+        if right {
+            self.right = child;
+        } else {
+            self.left = child;
+        }
+    }
+}
+
+/// A proof of the resolution of a tag under a root.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    /// The tag and record of the leaf the path of the proven tag ends in. This
+    /// is the tag itself if it is registered, and otherwise the registered tag
+    /// whose path shares the longest prefix with it. `None` if the registry is
+    /// empty.
+    pub leaf: Option<(String, Registry)>,
+    /// The siblings on the path of the leaf, each with the depth of its node,
+    /// from the leaf up to the root.
+    pub siblings: Vec<(u16, Hash)>,
+}
+
+impl MerkleProof {
+    /// Returns the root the proof leads to, or `None` if it is malformed.
+    pub fn root(&self, hash: impl Fn(&[u8]) -> Hash) -> Option<Hash> {
+        let Some((tag, registry)) = &self.leaf else {
+            return self.siblings.is_empty().then_some(EMPTY);
+        };
+        let key = leaf_key(tag, &hash);
+        let mut node = leaf_hash(tag, registry, &hash);
+        let mut below = DEPTH;
+        for (depth, sibling) in &self.siblings {
+            // The nodes must lead up towards the root.
+            if usize::from(*depth) >= below {
+                return None;
+            }
+            below = usize::from(*depth);
+            node = if bit(&key, below) {
+                node_hash(*depth, sibling, &node, &hash)
+            } else {
+                node_hash(*depth, &node, sibling, &hash)
+            };
+        }
+        Some(node)
+    }
+
+    /// Checks that `tag` is registered with `registry` under `root`.
+    pub fn verify_inclusion(
+        &self,
+        root: &Hash,
+        tag: &str,
+        registry: &Registry,
+        hash: impl Fn(&[u8]) -> Hash,
+    ) -> bool {
+        matches!(&self.leaf, Some((t, r)) if t == tag && r == registry)
+            && self.root(hash).as_ref() == Some(root)
+    }
+
+    /// Checks that `tag` is not registered under `root`.
+    pub fn verify_non_inclusion(
+        &self,
+        root: &Hash,
+        tag: &str,
+        hash: impl Fn(&[u8]) -> Hash,
+    ) -> bool {
+        if self.root(&hash).as_ref() != Some(root) {
+            return false;
+        }
+        let Some((leaf_tag, _)) = &self.leaf else {
+            return true;
+        };
+        let Some(depth) = first_difference(&leaf_key(tag, &hash), &leaf_key(leaf_tag, &hash))
+        else {
+            return false;
+        };
+        // The tag would be in the sibling of the node at the bit its path
+        // leaves the path of the leaf. Without such a node, there are no tags
+        // on its side.
+        !self.siblings.iter().any(|(d, _)| usize::from(*d) == depth)
+    }
+}
+
+/// An off-chain copy of the tree, which computes the root and builds proofs.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone)]
+pub struct MerkleTree {
+    /// The registered tags and their records, by their path.
+    leaves: collections::BTreeMap<Hash, (String, Registry)>,
+}
+
+#[cfg(feature = "std")]
+impl MerkleTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the canonical `tag` registered with `registry`, replacing its
+    /// previous record if any.
+    pub fn insert(&mut self, tag: String, registry: Registry) {
+        self.leaves.insert(leaf_key(&tag, sha256), (tag, registry));
+    }
+
+    /// Returns the root, which matches the `merkle_root` view of a registry
+    /// with the same tags and records.
+    pub fn root(&self) -> Hash {
+        let leaves: Vec<_> = self.leaves.iter().collect();
+        subtree_hash(&leaves, 0)
+    }
+
+    /// Returns the proof of the resolution of the canonical `tag`, which
+    /// proves its record if it is registered, and that it is not registered
+    /// otherwise.
+    pub fn proof(&self, tag: &str) -> MerkleProof {
+        let key = leaf_key(tag, sha256);
+        let mut leaves: Vec<_> = self.leaves.iter().collect();
+        let mut siblings = Vec::new();
+        while let [(first, _), .., (last, _)] = leaves[..] {
+            // The leaves are sorted, so the first and the last differ in the
+            // bit the node is at.
+            let Some(node) = first_difference(first, last) else {
+                break;
+            };
+            let split = leaves.partition_point(|(k, _)| !bit(k, node));
+            let right = leaves.split_off(split);
+            let (path, sibling) = if bit(&key, node) {
+                (right, leaves)
+            } else {
+                (leaves, right)
+            };
+            siblings.push((node as u16, subtree_hash(&sibling, node + 1)));
+            leaves = path;
+        }
+        siblings.reverse();
+        MerkleProof {
+            leaf: leaves.first().map(|(_, leaf)| (*leaf).clone()),
+            siblings,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromIterator<(String, Registry)> for MerkleTree {
+    fn from_iter<I: IntoIterator<Item = (String, Registry)>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        for (tag, registry) in iter {
+            tree.insert(tag, registry);
+        }
+        tree
+    }
+}
+
+/// Returns the hash of the subtree holding the sorted `leaves`, which share
+/// their first `depth` bits.
+#[cfg(feature = "std")]
+fn subtree_hash(leaves: &[(&Hash, &(String, Registry))], depth: usize) -> Hash {
+    match leaves {
+        [] => EMPTY,
+        [(_, (tag, registry))] => leaf_hash(tag, registry, sha256),
+        [(first, _), .., (last, _)] => {
+            let node = first_difference(first, last).unwrap_or(depth);
+            let split = leaves.partition_point(|(k, _)| !bit(k, node));
+            node_hash(
+                node as u16,
+                &subtree_hash(&leaves[..split], node + 1),
+                &subtree_hash(&leaves[split..], node + 1),
+                sha256,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(byte: u8) -> Registry {
+        Registry::new(
+            PublicKeyEd25519([byte; 32]),
+            ContractAddress::new(u64::from(byte), 0),
+            "provider".to_string(),
+        )
+    }
+
+    /// Returns `count` tags whose path starts with the bit `right`.
+    fn tags_on_side(right: bool, count: usize) -> Vec<String> {
+        (0..)
+            .map(|i| format!("tag{}", i))
+            .filter(|tag| bit(&leaf_key(tag, sha256), 0) == right)
+            .take(count)
+            .collect()
+    }
+
+    #[test]
+    fn non_inclusion_in_empty_tree() {
+        let tree = MerkleTree::new();
+        assert_eq!(tree.root(), EMPTY);
+
+        let proof = tree.proof("alice");
+        assert_eq!(proof.leaf, None);
+        assert!(proof.siblings.is_empty());
+        assert!(proof.verify_non_inclusion(&EMPTY, "alice", sha256));
+        assert!(!proof.verify_inclusion(&EMPTY, "alice", &record(1), sha256));
+
+        // An empty proof with siblings leads to no root.
+        let forged = MerkleProof {
+            leaf: None,
+            siblings: vec![(0, EMPTY)],
+        };
+        assert_eq!(forged.root(sha256), None);
+        assert!(!forged.verify_non_inclusion(&EMPTY, "alice", sha256));
+    }
+
+    #[test]
+    fn non_inclusion_with_single_leaf() {
+        let tree: MerkleTree = [("alice".to_string(), record(1))].into_iter().collect();
+        let root = tree.root();
+        assert_eq!(root, leaf_hash("alice", &record(1), sha256));
+
+        let proof = tree.proof("bob");
+        assert_eq!(proof.leaf, Some(("alice".to_string(), record(1))));
+        assert!(proof.siblings.is_empty());
+        assert!(proof.verify_non_inclusion(&root, "bob", sha256));
+
+        // The leaf itself can't be proven absent.
+        let proof = tree.proof("alice");
+        assert!(proof.verify_inclusion(&root, "alice", &record(1), sha256));
+        assert!(!proof.verify_non_inclusion(&root, "alice", sha256));
+    }
+
+    #[test]
+    fn non_inclusion_branching_at_root() {
+        // All tags are on the left of the root, the missing tag on its right.
+        let tags = tags_on_side(false, 3);
+        let missing = &tags_on_side(true, 1)[0];
+        let tree: MerkleTree = tags
+            .iter()
+            .enumerate()
+            .map(|(i, tag)| (tag.clone(), record(i as u8)))
+            .collect();
+        let root = tree.root();
+
+        let proof = tree.proof(missing);
+        assert!(proof.siblings.iter().all(|(depth, _)| *depth > 0));
+        assert!(proof.verify_non_inclusion(&root, missing, sha256));
+        for (i, tag) in tags.iter().enumerate() {
+            let proof = tree.proof(tag);
+            assert!(proof.verify_inclusion(&root, tag, &record(i as u8), sha256));
+            assert!(!proof.verify_non_inclusion(&root, tag, sha256));
+        }
+
+        // With a tag on the right of the root, the missing tag can no longer
+        // be proven absent through a tag on the left.
+        let right = &tags_on_side(true, 2)[1];
+        let mut tree = tree;
+        tree.insert(right.clone(), record(9));
+        let root = tree.root();
+        let proof = tree.proof(missing);
+        assert_eq!(proof.leaf, Some((right.clone(), record(9))));
+        assert!(proof.verify_non_inclusion(&root, missing, sha256));
+        let left_proof = tree.proof(&tags[0]);
+        assert!(!left_proof.verify_non_inclusion(&root, missing, sha256));
+    }
+
+    #[test]
+    fn malformed_proof_depths() {
+        let tags = tags_on_side(false, 2)
+            .into_iter()
+            .chain(tags_on_side(true, 1))
+            .collect::<Vec<_>>();
+        let tree: MerkleTree = tags
+            .iter()
+            .enumerate()
+            .map(|(i, tag)| (tag.clone(), record(i as u8)))
+            .collect();
+        let root = tree.root();
+        let proof = tree.proof(&tags[0]);
+        assert_eq!(proof.siblings.len(), 2);
+        assert_eq!(proof.root(sha256), Some(root));
+
+        // Siblings out of order.
+        let mut swapped = proof.clone();
+        swapped.siblings.reverse();
+        assert_eq!(swapped.root(sha256), None);
+        assert!(!swapped.verify_inclusion(&root, &tags[0], &record(0), sha256));
+
+        // Two siblings at the same depth.
+        let mut repeated = proof.clone();
+        repeated.siblings[1].0 = repeated.siblings[0].0;
+        assert_eq!(repeated.root(sha256), None);
+
+        // A sibling below the leaf.
+        let mut too_deep = proof.clone();
+        too_deep.siblings.insert(0, (DEPTH as u16, EMPTY));
+        assert_eq!(too_deep.root(sha256), None);
+        assert!(!too_deep.verify_non_inclusion(&root, &tags[2], sha256));
+    }
+}
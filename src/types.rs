@@ -1,34 +1,93 @@
 use crate::errors::Error;
-use concordium_std::SignatureEd25519;
 use concordium_std::{
-    Address, ContractAddress, PublicKeyEd25519, SchemaType, Serialize, Timestamp,
+    AccountAddress, AccountSignatures, Address, Amount, ContractAddress, Duration,
+    OwnedEntrypointName, PublicKeyEcdsaSecp256k1, PublicKeyEd25519, SchemaType, Serialize,
+    SignatureEcdsaSecp256k1, SignatureEd25519, Timestamp,
 };
-use core::fmt::Debug;
+use core::fmt::{self, Debug};
 
-/// Trait definition of the `IsMessage`. This trait is implemented for the two
-/// types `WithdrawMessage` and `TransferMessage`. The `isMessage` trait is used
-/// as an input parameter to the `validate_signature_and_increase_nonce`
-/// function so that the function works with both message types.
+/// Trait definition of the `IsMessage`. This trait is implemented for the
+/// parameters signed by the key of a tag, e.g. `RegisterParam`, `UpdateParam`
+/// or `SaleOffer`, so that the `validate_signature` function works with all of
+/// them. Returns the entry point and the submitter the message was signed for.
 pub trait IsMessage {
     fn expiry_time(&self) -> Timestamp;
+    /// The entry point that the message may be used with.
+    fn entry_point(&self) -> &OwnedEntrypointName;
+    /// The address that may submit the message, anyone if `None`.
+    fn submitter(&self) -> Option<Address>;
 }
 
 pub type RegistryResult<T> = Result<T, Error>;
 
-#[derive(Debug, Serialize, Clone, SchemaType)]
+/// A public key that a tag can be registered for, tagged by its signature
+/// scheme.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, SchemaType)]
+pub enum RegistryKey {
+    Ed25519(PublicKeyEd25519),
+    /// An ECDSA key over secp256k1, as used by Ethereum-style wallets.
+    Secp256k1(PublicKeyEcdsaSecp256k1),
+}
+
+impl fmt::Display for RegistryKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ed25519(key) => write!(f, "{}", key),
+            Self::Secp256k1(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+impl From<PublicKeyEd25519> for RegistryKey {
+    fn from(key: PublicKeyEd25519) -> Self {
+        Self::Ed25519(key)
+    }
+}
+
+impl From<PublicKeyEcdsaSecp256k1> for RegistryKey {
+    fn from(key: PublicKeyEcdsaSecp256k1) -> Self {
+        Self::Secp256k1(key)
+    }
+}
+
+/// The key of a tag in the contract state, the SHA-256 hash of the canonical
+/// tag, see [crate::tag_key]. It is also the path of the tag in the Merkle
+/// tree, see [crate::merkle::leaf_key].
+pub type TagKey = [u8; 32];
+
+/// A signature by a [RegistryKey], tagged by its signature scheme.
+#[derive(Debug, Serialize, Clone, Copy, SchemaType)]
+pub enum RegistrySignature {
+    Ed25519(SignatureEd25519),
+    Secp256k1(SignatureEcdsaSecp256k1),
+}
+
+impl From<SignatureEd25519> for RegistrySignature {
+    fn from(signature: SignatureEd25519) -> Self {
+        Self::Ed25519(signature)
+    }
+}
+
+impl From<SignatureEcdsaSecp256k1> for RegistrySignature {
+    fn from(signature: SignatureEcdsaSecp256k1) -> Self {
+        Self::Secp256k1(signature)
+    }
+}
+
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
 pub struct Registry {
-    pub public_key: PublicKeyEd25519,
+    pub public_key: RegistryKey,
     pub contract_address: ContractAddress,
     pub provider: String,
 }
 impl Registry {
     pub fn new(
-        public_key: PublicKeyEd25519,
+        public_key: impl Into<RegistryKey>,
         contract_address: ContractAddress,
         provider: String,
     ) -> Self {
         Self {
-            public_key,
+            public_key: public_key.into(),
             contract_address,
             provider,
         }
@@ -40,6 +99,13 @@ impl Registry {
 pub struct RegisterParam {
     pub tag: String,
     pub data: Registry,
+    /// The address that holds the CIS-2 token of the tag, typically the
+    /// account of the key holder.
+    pub owner: Address,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
     /// A timestamp to make the signatures expire.
     pub expiry_time: Timestamp,
 }
@@ -49,9 +115,9 @@ pub struct RegisterParam {
 #[cfg_attr(feature = "serde", derive(SerdeSerialize, SerdeDeserialize))]
 pub struct RegisterMessage {
     /// The signer public key.
-    pub signer: PublicKeyEd25519,
+    pub signer: RegistryKey,
     /// The signature.
-    pub signature: SignatureEd25519,
+    pub signature: RegistrySignature,
     /// The message being signed.
     pub message: RegisterParam,
 }
@@ -60,15 +126,1127 @@ impl IsMessage for RegisterParam {
     fn expiry_time(&self) -> Timestamp {
         self.expiry_time
     }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// Changes to the record of a tag, fields that are `None` are kept.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct RecordUpdate {
+    pub contract_address: Option<ContractAddress>,
+    pub provider: Option<String>,
+}
+
+/// Extrinsic parameter of an update signed by the key of the tag.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct UpdateParam {
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub nonce: u64,
+    pub update: RecordUpdate,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
+}
+
+/// The update message that is signed by the key of the tag.
+#[derive(Serialize, SchemaType)]
+pub struct UpdateMessage {
+    /// The signer public key.
+    pub signer: RegistryKey,
+    /// The signature.
+    pub signature: RegistrySignature,
+    /// The message being signed.
+    pub message: UpdateParam,
+}
+
+impl IsMessage for UpdateParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// The parts of a record that an operator may update.
+#[derive(Debug, Serialize, Clone, Copy, SchemaType, PartialEq, Eq)]
+pub enum UpdateScope {
+    ContractAddress,
+    Provider,
+}
+
+/// The rights of an operator of a tag.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct OperatorAuthorization {
+    /// The parts of the record the operator may update.
+    pub scopes: Vec<UpdateScope>,
+    /// The time after which the operator may no longer act, never if `None`.
+    pub until: Option<Timestamp>,
+}
+
+/// Extrinsic parameter to authorize or revoke an operator of a tag, signed by
+/// the key of the tag.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct TagOperatorParam {
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub nonce: u64,
+    pub operator: Address,
+    /// The rights of the operator, `None` revokes them.
+    pub authorization: Option<OperatorAuthorization>,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
+}
+
+/// The operator message that is signed by the key of the tag.
+#[derive(Serialize, SchemaType)]
+pub struct TagOperatorMessage {
+    /// The signer public key.
+    pub signer: RegistryKey,
+    /// The signature.
+    pub signature: RegistrySignature,
+    /// The message being signed.
+    pub message: TagOperatorParam,
+}
+
+impl IsMessage for TagOperatorParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// A guardian of a tag, which can help to recover the tag once its key is
+/// lost.
+#[derive(Debug, Serialize, Clone, Copy, SchemaType, PartialEq, Eq)]
+pub enum Guardian {
+    /// A key, which approves a recovery with a signed `RecoveryParam`.
+    Key(RegistryKey),
+    /// An address, which approves a recovery by sending it.
+    Address(Address),
+}
+
+/// The guardians of a key and the number of them needed for a recovery.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct GuardianConfig {
+    pub guardians: Vec<Guardian>,
+    pub threshold: u32,
+}
+
+/// Extrinsic parameter to set or remove the guardians of a tag, signed by the
+/// key of the tag.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct GuardiansParam {
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub nonce: u64,
+    /// The new guardians, `None` removes them.
+    pub config: Option<GuardianConfig>,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
 }
 
-/// The `WithdrawCcdEvent` is logged whenever a CCD amount held by a
-/// public key is withdrawn to an address.
+/// The guardians message that is signed by the key of the tag.
+#[derive(Serialize, SchemaType)]
+pub struct GuardiansMessage {
+    /// The signer public key.
+    pub signer: RegistryKey,
+    /// The signature.
+    pub signature: RegistrySignature,
+    /// The message being signed.
+    pub message: GuardiansParam,
+}
+
+impl IsMessage for GuardiansParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// The recovery of a tag to a new key that the guardians approve.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct RecoveryParam {
+    pub tag: String,
+    pub new_key: RegistryKey,
+    /// The recovery nonce of the tag, see `get_recovery_nonce`.
+    pub nonce: u64,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
+}
+
+impl IsMessage for RecoveryParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// Extrinsic parameter of the `approve_recovery` entry point.
+#[derive(Serialize, SchemaType)]
+pub struct ApproveRecoveryParam {
+    pub message: RecoveryParam,
+    /// The approvals of guardian keys, as signatures of the `message`.
+    pub signatures: Vec<(RegistryKey, RegistrySignature)>,
+}
+
+/// A pending recovery of a tag.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct Recovery {
+    pub new_key: RegistryKey,
+    /// The time of the first approval, which starts the recovery window.
+    pub started_at: Timestamp,
+    /// The guardians that approved the recovery.
+    pub approvals: Vec<Guardian>,
+}
+
+impl Recovery {
+    /// Returns the age of the recovery at `now`.
+    pub fn age(&self, now: Timestamp) -> Duration {
+        now.duration_since(self.started_at)
+            .unwrap_or(Duration::from_millis(0))
+    }
+}
+
+/// Extrinsic parameter to cancel a pending recovery, signed by the current key
+/// of the tag.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct CancelRecoveryParam {
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub nonce: u64,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
+}
+
+/// The cancel message that is signed by the key of the tag.
+#[derive(Serialize, SchemaType)]
+pub struct CancelRecoveryMessage {
+    /// The signer public key.
+    pub signer: RegistryKey,
+    /// The signature.
+    pub signature: RegistrySignature,
+    /// The message being signed.
+    pub message: CancelRecoveryParam,
+}
+
+impl IsMessage for CancelRecoveryParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// A sensitive change to the record of a tag, which only takes effect after
+/// the change delay.
+#[derive(Debug, Serialize, Clone, Copy, SchemaType, PartialEq, Eq)]
+pub enum Change {
+    ContractAddress(ContractAddress),
+    PublicKey(RegistryKey),
+    /// A sale offer listed with `list_sale`, by the hash the key of the tag
+    /// signs, see `get_offer_key_hash`. It is executed by `buy`.
+    Sale([u8; 32]),
+}
+
+/// A change in the pending-change queue of a tag.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct PendingChange {
+    /// The id of the change, unique across all tags.
+    pub id: u64,
+    pub change: Change,
+    /// The time from which the change can be executed.
+    pub executable_at: Timestamp,
+}
+
+/// Identifies a pending change of a tag.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct PendingChangeId {
+    pub tag: String,
+    pub id: u64,
+}
+
+/// Extrinsic parameter to move a tag to a new key, signed by both the current
+/// and the new key of the tag.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct RotateKeyParam {
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub nonce: u64,
+    pub new_key: RegistryKey,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
+}
+
+/// The rotate key message that is signed by the current and the new key.
+#[derive(Serialize, SchemaType)]
+pub struct RotateKeyMessage {
+    /// The current public key of the tag.
+    pub signer: RegistryKey,
+    /// The signature of the current key.
+    pub signature: RegistrySignature,
+    /// The signature of the new key, proving that it is held.
+    pub new_key_signature: RegistrySignature,
+    /// The message being signed.
+    pub message: RotateKeyParam,
+}
+
+impl IsMessage for RotateKeyParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// Extrinsic parameter to cancel a pending change, signed by the key of the
+/// tag.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct CancelPendingParam {
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub nonce: u64,
+    /// The id of the pending change.
+    pub id: u64,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
+}
+
+/// The cancel message that is signed by the key of the tag.
+#[derive(Serialize, SchemaType)]
+pub struct CancelPendingMessage {
+    /// The signer public key.
+    pub signer: RegistryKey,
+    /// The signature.
+    pub signature: RegistrySignature,
+    /// The message being signed.
+    pub message: CancelPendingParam,
+}
+
+impl IsMessage for CancelPendingParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// Extrinsic parameter of a change of the primary tag, signed by the key of
+/// the tag.
+#[derive(Serialize, Clone, SchemaType)]
+pub struct SetPrimaryParam {
+    /// The tag that becomes the primary tag of its key.
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub nonce: u64,
+    /// The entry point that the message is signed for.
+    pub entry_point: OwnedEntrypointName,
+    /// The only address that may submit the message, anyone if `None`.
+    pub submitter: Option<Address>,
+    /// A timestamp to make the signatures expire.
+    pub expiry_time: Timestamp,
+}
+
+/// The `set_primary` message that is signed by the key of the tag.
+#[derive(Serialize, SchemaType)]
+pub struct SetPrimaryMessage {
+    /// The signer public key.
+    pub signer: RegistryKey,
+    /// The signature.
+    pub signature: RegistrySignature,
+    /// The message being signed.
+    pub message: SetPrimaryParam,
+}
+
+impl IsMessage for SetPrimaryParam {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    fn submitter(&self) -> Option<Address> {
+        self.submitter
+    }
+}
+
+/// A registration imported from an older registry instance.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct ImportEntry {
+    pub tag: String,
+    pub data: Registry,
+    /// The address that registered the tag in the older instance.
+    pub registrar: Address,
+    /// The holder of the tag token.
+    pub owner: Address,
+    pub registered_at: Timestamp,
+}
+
+/// Extrinsic parameter of the `import` entry point.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct ImportParam {
+    pub entries: Vec<ImportEntry>,
+}
+
+/// CCD paid to a tag before it was registered, see `escrow`.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub struct Escrow {
+    /// The account that paid, refunded if the tag is not registered in time.
+    pub sender: AccountAddress,
+    pub amount: Amount,
+    /// The slot time from which the sender can take the CCD back.
+    pub refundable_at: Timestamp,
+}
+
+/// Extrinsic parameter of the `set_moderator` entry point.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct SetModeratorParam {
+    pub moderator: Address,
+    /// Whether the address is given or stripped of the dispute role.
+    pub enabled: bool,
+}
+
+/// Extrinsic parameter of the `freeze` entry point.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct FreezeParam {
+    pub tag: String,
+    /// A reference to the case, e.g. of a fraud or trademark claim.
+    pub case_reference: String,
+}
+
+/// Extrinsic parameter of the `reassign` entry point.
+#[derive(Serialize, SchemaType)]
+pub struct ReassignParam {
+    /// The registration of the tag for the new key, signed by that key. Its
+    /// `owner` becomes the new holder of the tag token.
+    pub message: RegisterMessage,
+}
+
+/// Extrinsic parameter of the `operator_update` entry point.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct OperatorUpdateParam {
+    pub tag: String,
+    pub update: RecordUpdate,
+}
+
+/// Query parameter of the `get_tag_operator` view.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct TagOperatorQuery {
+    pub tag: String,
+    pub operator: Address,
+}
+
+/// The `RegisterEvent` is logged whenever a tag is registered.
 #[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
 pub struct RegisterEvent {
+    /// The canonical tag, as stored in the registry.
+    pub tag: String,
+    pub public_key: RegistryKey,
+    pub contract_address: ContractAddress,
+    pub provider: String,
+    pub registrar: Address,
+}
+
+/// The layout of the `RegisterEvent` before tags could be registered for
+/// secp256k1 keys, logged with the tag `28`. It is kept to parse old event
+/// logs.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct LegacyRegisterEvent {
+    /// The canonical tag, as stored in the registry.
     pub tag: String,
     pub public_key: PublicKeyEd25519,
     pub contract_address: ContractAddress,
     pub provider: String,
     pub registrar: Address,
 }
+
+/// Parameter of the `list_tags` view.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct ListTagsParam {
+    /// The last tag of the previous page, `None` for the first page.
+    pub cursor: Option<String>,
+    /// The maximum number of entries to return.
+    pub limit: u32,
+}
+
+/// The dispute a tag is frozen for.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct Freeze {
+    /// A reference to the case, e.g. of a fraud or trademark claim.
+    pub case_reference: String,
+    /// The moderator that froze the tag.
+    pub moderator: Address,
+    pub frozen_at: Timestamp,
+}
+
+/// The tags registered for a key.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct KeyTags {
+    /// The tag returned by `get_tag`, see `set_primary`. Initially the first
+    /// tag registered for the key.
+    pub primary: String,
+    /// All tags of the key, in the order they were bound to it.
+    pub tags: Vec<String>,
+}
+
+/// A [Registry] record as it is kept in the contract state.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct RegistryEntry {
+    /// The canonical tag. The state is keyed by its [TagKey], so this is the
+    /// only place the tag is stored.
+    pub tag: String,
+    pub registry: Registry,
+    /// The address that submitted the registration.
+    pub registrar: Address,
+    /// The address holding the CIS-2 token of the tag, initially the owner
+    /// signed by the key in the registration.
+    pub owner: Address,
+    /// The next nonce of the messages signed by the key of the tag, such as
+    /// `UpdateMessage`.
+    pub nonce: u64,
+    /// The dispute the tag is frozen for, if any. No changes are accepted
+    /// while it is frozen.
+    pub frozen: Option<Freeze>,
+    /// The slot time of the block the tag was registered in.
+    pub registered_at: Timestamp,
+    /// The slot time of the block the record was last changed in.
+    pub updated_at: Timestamp,
+    /// The position of the tag in the order of registration, which
+    /// `list_tags` pages through. It is assigned when the tag is inserted.
+    pub position: u64,
+}
+
+/// A registered tag together with its [Registry] record and metadata.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct TagRecord {
+    /// The canonical tag.
+    pub tag: String,
+    pub registry: Registry,
+    pub registrar: Address,
+    pub owner: Address,
+    pub frozen: Option<Freeze>,
+    pub registered_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+impl TagRecord {
+    pub fn new(entry: RegistryEntry) -> Self {
+        Self {
+            tag: entry.tag,
+            registry: entry.registry,
+            registrar: entry.registrar,
+            owner: entry.owner,
+            frozen: entry.frozen,
+            registered_at: entry.registered_at,
+            updated_at: entry.updated_at,
+        }
+    }
+}
+
+/// A page of registered tags returned by the `list_tags` view.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct ListTagsResponse {
+    pub tags: Vec<TagRecord>,
+    /// The cursor of the next page, that is the last tag of this page, `None`
+    /// if this is the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Global counters kept in the contract state.
+#[derive(Debug, Serialize, Clone, Copy, Default, SchemaType)]
+pub struct RegistryStats {
+    /// The number of tags in the registry.
+    pub total_tags: u64,
+    /// The number of tag tokens transferred with the CIS-2 `transfer`.
+    pub transfers: u64,
+    /// The number of tags bought through a sale offer.
+    pub sales: u64,
+    /// The number of tags reassigned by a moderator.
+    pub reassignments: u64,
+    /// The number of tags recovered by their guardians.
+    pub recoveries: u64,
+}
+
+/// The anti-spam limits on registrations, see `set_quotas`.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct Quotas {
+    /// The length of the windows the quotas are counted over.
+    pub window: Duration,
+    /// The maximum number of tags registered through a provider per window,
+    /// `None` for no limit.
+    pub per_provider: Option<u64>,
+    /// The maximum number of tags registered by a sender per window, `None`
+    /// for no limit.
+    pub per_sender: Option<u64>,
+    /// The minimum time between two registrations by the same sender.
+    pub cooldown: Duration,
+}
+
+/// The registrations of a provider or a sender in the current quota window.
+#[derive(Debug, Serialize, Clone, SchemaType, PartialEq, Eq)]
+pub struct Usage {
+    /// The slot time the current window started at.
+    pub window_start: Timestamp,
+    /// The number of registrations in the current window.
+    pub registrations: u64,
+    /// The slot time of the latest registration.
+    pub last_registration: Timestamp,
+}
+
+/// Parameter of the `stats` view.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct StatsParam {
+    /// The providers to report tag counts for.
+    pub providers: Vec<String>,
+    /// The namespaces to report tag counts for, e.g. `ccd`.
+    pub namespaces: Vec<String>,
+}
+
+/// The registry statistics returned by the `stats` view.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct StatsResponse {
+    pub total_tags: u64,
+    pub transfers: u64,
+    pub sales: u64,
+    pub reassignments: u64,
+    pub recoveries: u64,
+    /// The number of tags for each requested provider.
+    pub providers: Vec<(String, u64)>,
+    /// The number of tags for each requested namespace.
+    pub namespaces: Vec<(String, u64)>,
+}
+
+/// The message signed by an account for the `permit` entry point.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct PermitMessage {
+    /// The contract the message is intended for.
+    pub contract_address: ContractAddress,
+    /// The nonce of the signer, to prevent replay attacks.
+    pub nonce: u64,
+    /// A timestamp to make the signatures expire.
+    pub timestamp: Timestamp,
+    /// The entry point the message is intended for.
+    pub entry_point: OwnedEntrypointName,
+    /// The serialized parameter of the entry point.
+    #[concordium(size_length = 2)]
+    pub payload: Vec<u8>,
+}
+
+/// Extrinsic parameter of the `permit` entry point, as defined by CIS-3.
+#[derive(Serialize, SchemaType)]
+pub struct PermitParam {
+    /// The signatures of the signer, CIS-3 supports multi-sig accounts.
+    pub signature: AccountSignatures,
+    /// The account that signed the message.
+    pub signer: AccountAddress,
+    /// The message being signed.
+    pub message: PermitMessage,
+}
+
+/// The leading part of a [PermitParam], used to read the signer before the
+/// message bytes.
+#[derive(Serialize)]
+pub struct PermitParamPartial {
+    pub signature: AccountSignatures,
+    pub signer: AccountAddress,
+}
+
+/// Parameter of the `supportsPermit` view.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct SupportsPermitQueryParams {
+    #[concordium(size_length = 2)]
+    pub queries: Vec<OwnedEntrypointName>,
+}
+
+/// Parameter of the `nonceOf` view.
+#[derive(Debug, Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct VecOfAccountAddresses {
+    #[concordium(size_length = 2)]
+    pub queries: Vec<AccountAddress>,
+}
+
+/// The next nonce of each queried account, returned by the `nonceOf` view.
+#[derive(Debug, Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct NonceOfQueryResponse(#[concordium(size_length = 2)] pub Vec<u64>);
+
+/// The `NonceEvent` is logged whenever a `permit` message or a sale offer is
+/// executed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct NonceEvent {
+    /// The account that signed the message.
+    pub account: AccountAddress,
+    /// The nonce that was used by the message.
+    pub nonce: u64,
+}
+
+/// A pending commitment of the commit–reveal registration flow.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct Commitment {
+    /// The address that made the commitment.
+    pub committer: Address,
+    /// The slot time of the block the commitment was made in.
+    pub committed_at: Timestamp,
+}
+
+impl Commitment {
+    /// Returns the age of the commitment at `now`.
+    pub fn age(&self, now: Timestamp) -> Duration {
+        now.duration_since(self.committed_at)
+            .unwrap_or(Duration::from_millis(0))
+    }
+}
+
+/// The data hashed into a commitment. The tag is hashed in its canonical form.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct CommitmentPreimage {
+    pub tag: String,
+    /// The key the tag is going to be registered for.
+    pub public_key: RegistryKey,
+    /// A secret that keeps the tag from being guessed from the commitment.
+    pub salt: [u8; 32],
+}
+
+/// Extrinsic parameter of the `reveal` entry point.
+#[derive(Serialize, SchemaType)]
+pub struct RevealParam {
+    /// The signed registration of the committed tag.
+    pub message: RegisterMessage,
+    /// The salt that was used in the commitment.
+    pub salt: [u8; 32],
+}
+
+/// The `CommitEvent` is logged whenever a commitment is made.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct CommitEvent {
+    pub commitment: [u8; 32],
+    pub committer: Address,
+}
+
+/// The `RevealEvent` is logged whenever a commitment is revealed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct RevealEvent {
+    pub commitment: [u8; 32],
+    /// The canonical tag that was committed to.
+    pub tag: String,
+}
+
+/// A bid on the auction of a premium tag.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct Bid {
+    /// The account that placed the bid, refunded if it is outbid.
+    pub bidder: AccountAddress,
+    pub amount: Amount,
+    /// The record the tag is registered with if the bid wins.
+    pub data: Registry,
+    /// The holder of the tag token if the bid wins.
+    pub owner: Address,
+}
+
+/// A running auction of a premium tag.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct Auction {
+    /// The time after which no more bids are accepted.
+    pub end: Timestamp,
+    /// The minimum amount of the first bid.
+    pub reserve_price: Amount,
+    pub highest_bid: Option<Bid>,
+}
+
+/// Extrinsic parameter of the `set_premium_tags` entry point.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct SetPremiumTagsParam {
+    pub tags: Vec<String>,
+    /// Whether the tags are designated as premium or released from it.
+    pub premium: bool,
+}
+
+/// Extrinsic parameter of the `start_auction` entry point.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct StartAuctionParam {
+    pub tag: String,
+    /// The time after which no more bids are accepted.
+    pub end: Timestamp,
+    /// The minimum amount of the first bid.
+    pub reserve_price: Amount,
+}
+
+/// The `PremiumTagEvent` is logged whenever a tag is designated as premium or
+/// released from it.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct PremiumTagEvent {
+    pub tag: String,
+    pub premium: bool,
+}
+
+/// The `AuctionStartedEvent` is logged whenever an auction is started.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct AuctionStartedEvent {
+    pub tag: String,
+    pub end: Timestamp,
+    pub reserve_price: Amount,
+}
+
+/// The `BidEvent` is logged whenever a bid is placed on an auction.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct BidEvent {
+    pub tag: String,
+    pub bidder: AccountAddress,
+    pub amount: Amount,
+}
+
+/// The `AuctionSettledEvent` is logged whenever an auction is settled.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct AuctionSettledEvent {
+    pub tag: String,
+    /// The winning bidder, `None` if the tag was not registered.
+    pub winner: Option<AccountAddress>,
+    /// The amount paid by the winner.
+    pub amount: Amount,
+}
+
+/// An offer to sell a tag at a fixed price, signed by its holder with its
+/// account keys and, to list it with `list_sale`, by the key of the tag.
+#[derive(Debug, Serialize, Clone, SchemaType)]
+pub struct SaleOffer {
+    /// The contract the offer is intended for.
+    pub contract_address: ContractAddress,
+    /// The account holding the tag, which receives the payment.
+    pub seller: AccountAddress,
+    /// The next `permit` nonce of the seller.
+    pub nonce: u64,
+    pub tag: String,
+    /// The nonce of the tag, see `get_key`.
+    pub tag_nonce: u64,
+    pub price: Amount,
+    /// The only account allowed to buy the tag, anyone if `None`.
+    pub buyer: Option<AccountAddress>,
+    /// The entry point that the key of the tag signs the offer for, that is
+    /// `list_sale`.
+    pub entry_point: OwnedEntrypointName,
+    pub expiry_time: Timestamp,
+}
+
+impl IsMessage for SaleOffer {
+    fn expiry_time(&self) -> Timestamp {
+        self.expiry_time
+    }
+
+    fn entry_point(&self) -> &OwnedEntrypointName {
+        &self.entry_point
+    }
+
+    /// Anyone may list the offer, the buyer is checked by `buy`.
+    fn submitter(&self) -> Option<Address> {
+        None
+    }
+}
+
+/// The sale offer that is signed by the key of the tag to list it.
+#[derive(Serialize, SchemaType)]
+pub struct ListSaleMessage {
+    /// The public key of the tag.
+    pub signer: RegistryKey,
+    /// The signature of the key over the offer, see `get_offer_key_hash`.
+    pub signature: RegistrySignature,
+    /// The message being signed.
+    pub message: SaleOffer,
+}
+
+/// Extrinsic parameter of the `buy` entry point.
+#[derive(Serialize, SchemaType)]
+pub struct BuyParam {
+    /// The signature of the seller over the offer.
+    pub signature: AccountSignatures,
+    /// The offer, which must be listed with `list_sale`.
+    pub offer: SaleOffer,
+    /// The registration of the tag for the key of the buyer, signed by that
+    /// key.
+    pub message: RegisterMessage,
+}
+
+/// The `SaleEvent` is logged whenever a tag is bought through a sale offer.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct SaleEvent {
+    pub tag: String,
+    pub seller: AccountAddress,
+    pub buyer: AccountAddress,
+    /// The price paid by the buyer, including the fee.
+    pub price: Amount,
+    /// The part of the price paid to the admin.
+    pub fee: Amount,
+    /// The new record of the tag.
+    pub registry: Registry,
+}
+
+/// The `MetadataUrlEvent` is logged whenever the base of the token metadata
+/// URLs is changed. The metadata URLs of all tag tokens change with it.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct MetadataUrlEvent {
+    pub url: String,
+}
+
+/// The `ProtocolFeeEvent` is logged whenever the protocol fee is changed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ProtocolFeeEvent {
+    /// The fee in basis points of the price.
+    pub fee: u16,
+}
+
+/// The `UpdateEvent` is logged whenever the record of a tag is updated.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct UpdateEvent {
+    pub tag: String,
+    /// The updated record.
+    pub registry: Registry,
+    /// The address that submitted the update, e.g. an operator of the tag.
+    pub updated_by: Address,
+}
+
+/// The `TagOperatorEvent` is logged whenever an operator of a tag is
+/// authorized or revoked.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct TagOperatorEvent {
+    pub tag: String,
+    /// The key that authorized the operator.
+    pub public_key: RegistryKey,
+    pub operator: Address,
+    /// The rights of the operator, `None` if they were revoked.
+    pub authorization: Option<OperatorAuthorization>,
+}
+
+/// The `GuardiansEvent` is logged whenever the guardians of a tag are set or
+/// removed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct GuardiansEvent {
+    pub tag: String,
+    /// The key the guardians belong to.
+    pub public_key: RegistryKey,
+    /// The new guardians, `None` if they were removed.
+    pub config: Option<GuardianConfig>,
+}
+
+/// The `RecoveryApprovedEvent` is logged whenever guardians approve the
+/// recovery of a tag.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct RecoveryApprovedEvent {
+    pub tag: String,
+    pub new_key: RegistryKey,
+    /// The guardians that approved it in this transaction.
+    pub guardians: Vec<Guardian>,
+    /// The total number of approvals of the recovery.
+    pub approvals: u32,
+}
+
+/// The `RecoveryCancelledEvent` is logged whenever the key of a tag cancels
+/// its pending recovery.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct RecoveryCancelledEvent {
+    pub tag: String,
+    pub new_key: RegistryKey,
+}
+
+/// The `RecoveredEvent` is logged whenever a tag is recovered to a new key.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct RecoveredEvent {
+    pub tag: String,
+    pub old_key: RegistryKey,
+    pub new_key: RegistryKey,
+}
+
+/// The `ChangeQueuedEvent` is logged whenever a change is added to the
+/// pending-change queue of a tag.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ChangeQueuedEvent {
+    pub tag: String,
+    pub change: PendingChange,
+}
+
+/// The `ChangeCancelledEvent` is logged whenever the key of a tag cancels a
+/// pending change.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ChangeCancelledEvent {
+    pub tag: String,
+    pub id: u64,
+}
+
+/// The `ChangeExecutedEvent` is logged whenever a pending change takes effect.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ChangeExecutedEvent {
+    pub tag: String,
+    pub id: u64,
+    /// The updated record.
+    pub registry: Registry,
+}
+
+/// The `ChangeDelayEvent` is logged whenever the change delay is changed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ChangeDelayEvent {
+    pub delay: Duration,
+}
+
+/// The `ModeratorEvent` is logged whenever an address is given or stripped of
+/// the dispute role.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ModeratorEvent {
+    pub moderator: Address,
+    pub enabled: bool,
+}
+
+/// The `FrozenEvent` is logged whenever a tag is frozen, or the case reference
+/// of a frozen tag is changed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct FrozenEvent {
+    pub tag: String,
+    pub freeze: Freeze,
+}
+
+/// The `UnfrozenEvent` is logged whenever a tag is unfrozen without being
+/// reassigned.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct UnfrozenEvent {
+    pub tag: String,
+    pub moderator: Address,
+}
+
+/// The `ReassignedEvent` is logged whenever a moderator reassigns a tag to a
+/// new key, which also unfreezes it.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ReassignedEvent {
+    pub tag: String,
+    /// The new record of the tag.
+    pub registry: Registry,
+    /// The new holder of the tag token.
+    pub owner: Address,
+    pub moderator: Address,
+}
+
+/// The `PrimaryEvent` is logged whenever a key chooses its primary tag.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct PrimaryEvent {
+    pub key: RegistryKey,
+    pub tag: String,
+}
+
+/// The `ImportSealedEvent` is logged when the import phase is sealed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ImportSealedEvent {
+    /// The number of tags in the registry at the end of the import phase.
+    pub total_tags: u64,
+}
+
+/// The `EscrowedEvent` is logged whenever CCD is paid to a tag that is not
+/// registered yet.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct EscrowedEvent {
+    pub tag: String,
+    pub sender: AccountAddress,
+    pub amount: Amount,
+    pub refundable_at: Timestamp,
+}
+
+/// The `EscrowReleasedEvent` is logged whenever the escrow of a tag is
+/// deposited into its wallet after the tag was registered.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct EscrowReleasedEvent {
+    pub tag: String,
+    /// The total amount of the escrow.
+    pub amount: Amount,
+}
+
+/// The `EscrowRefundedEvent` is logged whenever a sender takes back the CCD it
+/// paid to a tag that was not registered in time.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct EscrowRefundedEvent {
+    pub tag: String,
+    pub sender: AccountAddress,
+    pub amount: Amount,
+}
+
+/// The `QuotasEvent` is logged whenever the quotas on registrations are
+/// changed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct QuotasEvent {
+    pub quotas: Quotas,
+}
@@ -1,11 +1,31 @@
+use concordium_cis2::*;
 use concordium_smart_contract_testing::*;
-use concordium_std::{PublicKeyEd25519, SignatureEd25519};
+use concordium_std::{
+    collections::BTreeMap, AccountSignatures, CredentialSignatures, PublicKeyEcdsaSecp256k1,
+    PublicKeyEd25519, Signature, SignatureEcdsaSecp256k1, SignatureEd25519,
+};
 use registry::*;
-use types::{RegisterMessage, RegisterParam, Registry};
+use types::{
+    ApproveRecoveryParam, Auction, BuyParam, CancelPendingMessage, CancelPendingParam,
+    CancelRecoveryMessage, CancelRecoveryParam, Change, ChangeQueuedEvent, CommitmentPreimage,
+    Escrow, Freeze, FreezeParam, Guardian, GuardianConfig, GuardiansMessage, GuardiansParam,
+    ImportEntry, ImportParam, KeyTags, LegacyRegisterEvent, ListSaleMessage, ListTagsParam,
+    ListTagsResponse, NonceEvent, NonceOfQueryResponse, OperatorAuthorization, OperatorUpdateParam,
+    PendingChange, PendingChangeId, PermitMessage, PermitParam, Quotas, ReassignParam,
+    RecordUpdate, Recovery, RecoveryParam, RegisterEvent, RegisterMessage, RegisterParam, Registry,
+    RegistryEntry, RegistryKey, RegistrySignature, RevealParam, RotateKeyMessage, RotateKeyParam,
+    SaleOffer, SetModeratorParam, SetPremiumTagsParam, SetPrimaryMessage, SetPrimaryParam,
+    StartAuctionParam, StatsParam, StatsResponse, TagOperatorMessage, TagOperatorParam, TagRecord,
+    UpdateEvent, UpdateMessage, UpdateParam, UpdateScope, VecOfAccountAddresses,
+};
 
 /// A test account.
 const ALICE: AccountAddress = AccountAddress([0u8; 32]);
 const ALICE_ADDR: Address = Address::Account(ALICE);
+/// A second test account.
+const BOB: AccountAddress = AccountAddress([1u8; 32]);
+/// A third test account.
+const CAROL: AccountAddress = AccountAddress([2u8; 32]);
 
 /// The initial balance of the ALICE test account.
 const ACC_INITIAL_BALANCE: Amount = Amount::from_ccd(10_000);
@@ -42,7 +62,8 @@ fn test_get_registry_by_tag() {
             },
         )
         .expect("Update succeeds with `false` as input.");
-    let registry: Registry = update.parse_return_value().expect("Deserialize `Error`");
+    let entry: RegistryEntry = update.parse_return_value().expect("Deserialize `Error`");
+    let registry = entry.registry;
     assert_eq!(registry.public_key, sim_registry.public_key);
     assert_eq!(registry.contract_address, sim_registry.contract_address);
     assert_eq!(registry.provider, sim_registry.provider);
@@ -77,7 +98,8 @@ fn test_append_ccd_to_end_of_tag() {
             },
         )
         .expect("Update succeeds with `false` as input.");
-    let registry: Registry = update.parse_return_value().expect("Deserialize `Error`");
+    let entry: RegistryEntry = update.parse_return_value().expect("Deserialize `Error`");
+    let registry = entry.registry;
     assert_eq!(registry.public_key, sim_registry.public_key);
     assert_eq!(registry.contract_address, sim_registry.contract_address);
     assert_eq!(registry.provider, sim_registry.provider);
@@ -133,6 +155,9 @@ fn test_cannot_register_a_tag_for_another_user() {
     let param = RegisterParam {
         tag,
         data: registry,
+        owner: Address::Account(ALICE),
+        entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+        submitter: None,
         expiry_time: Timestamp::from_timestamp_millis(5000),
     };
     // Get the message hash to be signed.
@@ -155,8 +180,8 @@ fn test_cannot_register_a_tag_for_another_user() {
     let signature = signing_key.sign(&invoke.return_value);
 
     let message = RegisterMessage {
-        signer: alice_public_key,
-        signature: SignatureEd25519(signature.to_bytes()),
+        signer: alice_public_key.into(),
+        signature: SignatureEd25519(signature.to_bytes()).into(),
         message: param,
     };
     let payload = UpdateContractPayload {
@@ -224,6 +249,9 @@ fn test_cannot_create_tag_that_exists() {
     let param = RegisterParam {
         tag,
         data: registry,
+        owner: Address::Account(ALICE),
+        entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+        submitter: None,
         expiry_time: Timestamp::from_timestamp_millis(5000),
     };
     // Get the message hash to be signed.
@@ -246,8 +274,8 @@ fn test_cannot_create_tag_that_exists() {
     let signature = signing_key.sign(&invoke.return_value);
 
     let message = RegisterMessage {
-        signer: alice_public_key,
-        signature: SignatureEd25519(signature.to_bytes()),
+        signer: alice_public_key.into(),
+        signature: SignatureEd25519(signature.to_bytes()).into(),
         message: param,
     };
     let payload = UpdateContractPayload {
@@ -290,6 +318,9 @@ fn test_cannot_verify_signature() {
     let param = RegisterParam {
         tag: "bob.ccd".into(),
         data: registry,
+        owner: Address::Account(ALICE),
+        entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+        submitter: None,
         expiry_time: Timestamp::from_timestamp_millis(5000),
     };
     // Get the message hash to be signed.
@@ -312,8 +343,8 @@ fn test_cannot_verify_signature() {
     let signature = signing_key.sign(&invoke.return_value);
 
     let message = RegisterMessage {
-        signer: alice_public_key,
-        signature: SignatureEd25519(signature.to_bytes()),
+        signer: alice_public_key.into(),
+        signature: SignatureEd25519(signature.to_bytes()).into(),
         message: param,
     };
     let payload = UpdateContractPayload {
@@ -333,7 +364,7 @@ fn test_cannot_verify_signature() {
 }
 
 #[test]
-fn test_cannot_register_a_key_twice() {
+fn test_register_several_tags_for_a_key() {
     use ed25519_dalek::{Signer, SigningKey};
 
     // Initialize the test chain.
@@ -356,6 +387,9 @@ fn test_cannot_register_a_key_twice() {
     let param = RegisterParam {
         tag,
         data: registry.clone(),
+        owner: Address::Account(ALICE),
+        entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+        submitter: None,
         expiry_time: Timestamp::from_timestamp_millis(5000),
     };
     // Get the message hash to be signed.
@@ -378,8 +412,8 @@ fn test_cannot_register_a_key_twice() {
     let signature = signing_key.sign(&invoke.return_value);
 
     let message = RegisterMessage {
-        signer: alice_public_key,
-        signature: SignatureEd25519(signature.to_bytes()),
+        signer: alice_public_key.into(),
+        signature: SignatureEd25519(signature.to_bytes()).into(),
         message: param,
     };
     let payload = UpdateContractPayload {
@@ -396,6 +430,9 @@ fn test_cannot_register_a_key_twice() {
     let param = RegisterParam {
         tag: "alice_new_key.ccd".into(),
         data: registry,
+        owner: Address::Account(ALICE),
+        entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+        submitter: None,
         expiry_time: Timestamp::from_timestamp_millis(5000),
     };
     // Get the message hash to be signed.
@@ -418,8 +455,8 @@ fn test_cannot_register_a_key_twice() {
     let signature = signing_key.sign(&invoke.return_value);
 
     let message = RegisterMessage {
-        signer: alice_public_key,
-        signature: SignatureEd25519(signature.to_bytes()),
+        signer: alice_public_key.into(),
+        signature: SignatureEd25519(signature.to_bytes()).into(),
         message: param,
     };
     let payload = UpdateContractPayload {
@@ -428,12 +465,3155 @@ fn test_cannot_register_a_key_twice() {
         receive_name: OwnedReceiveName::new_unchecked("registry.register".to_string()),
         message: OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
     };
+    chain
+        .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+        .expect("Registering a second tag for the key succeeds");
+
+    let view = |chain: &Chain, entrypoint: &str, message: OwnedParameter| {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+                    message,
+                },
+            )
+            .expect("Should be able to query the view")
+    };
+    let key = RegistryKey::from(alice_public_key);
+    let key_param = OwnedParameter::from_serial(&key).expect("Parameter within size bounds");
+    let tags: KeyTags = view(&chain, "get_tags", key_param.clone())
+        .parse_return_value()
+        .expect("Deserialize `KeyTags`");
+    assert_eq!(
+        tags,
+        KeyTags {
+            primary: "alice.ccd".into(),
+            tags: vec!["alice.ccd".into(), "alice_new_key.ccd".into()],
+        }
+    );
+
+    // The key makes its second tag the primary one.
+    let message = SetPrimaryParam {
+        tag: "alice_new_key".into(),
+        nonce: 0,
+        entry_point: OwnedEntrypointName::new_unchecked("set_primary".into()),
+        submitter: None,
+        expiry_time: Timestamp::from_timestamp_millis(5000),
+    };
+    let hash = view(
+        &chain,
+        "get_set_primary_hash",
+        OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+    )
+    .return_value;
+    let param = SetPrimaryMessage {
+        signer: key,
+        signature: SignatureEd25519(signing_key.sign(&hash).to_bytes()).into(),
+        message,
+    };
+    let payload = UpdateContractPayload {
+        address: init.contract_address,
+        amount: Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("registry.set_primary".to_string()),
+        message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+    };
+    chain
+        .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+        .expect("Setting the primary tag succeeds");
+    let primary: String = view(&chain, "get_tag", key_param)
+        .parse_return_value()
+        .expect("Deserialize tag");
+    assert_eq!(primary, "alice_new_key.ccd");
+}
+
+#[test]
+fn test_list_tags_in_pages() {
+    let (mut chain, init) = initialize();
+    for tag in ["alice", "bob", "carol"] {
+        register_tag(&mut chain, &init, tag.into());
+    }
+    let list = |chain: &Chain, cursor: Option<String>| -> ListTagsResponse {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    address: init.contract_address,
+                    amount: Amount::zero(),
+                    receive_name: OwnedReceiveName::new_unchecked("registry.list_tags".to_string()),
+                    message: OwnedParameter::from_serial(&ListTagsParam { cursor, limit: 2 })
+                        .expect("Parameter within size bounds"),
+                },
+            )
+            .expect("Listing tags succeeds")
+            .parse_return_value()
+            .expect("Deserialize response")
+    };
+
+    let page = list(&chain, None);
+    let mut tags: Vec<String> = page.tags.into_iter().map(|record| record.tag).collect();
+    assert_eq!(page.next_cursor, Some("bob.ccd".into()));
+
+    // A tag registered between two pages is listed last, neither skipping nor
+    // repeating a tag.
+    register_tag(&mut chain, &init, "aaron".into());
+    let mut cursor = page.next_cursor;
+    while let Some(next) = cursor {
+        let page = list(&chain, Some(next));
+        assert!(page.tags.len() <= 2);
+        tags.extend(page.tags.into_iter().map(|record| record.tag));
+        cursor = page.next_cursor;
+    }
+    assert_eq!(tags, vec!["alice.ccd", "bob.ccd", "carol.ccd", "aaron.ccd"]);
+}
+
+#[test]
+fn test_stats_count_registrations() {
+    let (mut chain, init) = initialize();
+    for tag in ["alice", "bob"] {
+        register_tag(&mut chain, &init, tag.into());
+    }
+
+    let param = StatsParam {
+        providers: vec!["AfrixLabs".into(), "Unknown".into()],
+        namespaces: vec!["ccd".into()],
+    };
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                address: init.contract_address,
+                amount: Amount::zero(),
+                receive_name: OwnedReceiveName::new_unchecked("registry.stats".to_string()),
+                message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Querying stats succeeds");
+    let stats: StatsResponse = invoke.parse_return_value().expect("Deserialize response");
+    assert_eq!(stats.total_tags, 2);
+    assert_eq!(
+        stats.providers,
+        vec![("AfrixLabs".to_string(), 2), ("Unknown".to_string(), 0)]
+    );
+    assert_eq!(stats.namespaces, vec![("ccd".to_string(), 2)]);
+    assert_eq!(stats.transfers, 0);
+
+    // Transfers of the tag tokens are counted.
+    let bob_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    let message = signed_register_message(&chain, &init, "alice".into(), "transfer", &bob_key, BOB);
+    let param = TransferParams(vec![concordium_cis2::Transfer {
+        token_id: tag_token_id("alice.ccd"),
+        amount: TokenAmountU8(1),
+        from: ALICE_ADDR,
+        to: Receiver::Account(BOB),
+        data: AdditionalData::from(to_bytes(&message)),
+    }]);
+    let payload = UpdateContractPayload {
+        address: init.contract_address,
+        amount: Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("registry.transfer".to_string()),
+        message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+    };
+    chain
+        .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+        .expect("Transferring succeeds");
+    let stats = get_stats(&chain, &init);
+    assert_eq!(stats.transfers, 1);
+    assert_eq!(
+        (stats.sales, stats.reassignments, stats.recoveries),
+        (0, 0, 0)
+    );
+}
+
+#[test]
+fn test_quotas_limit_registrations() {
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    let rng = &mut rand::thread_rng();
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let mut register = |chain: &mut Chain, sender: AccountAddress, tag: &str| {
+        let key = ed25519::SigningKey::generate(rng);
+        let message = signed_register_message(chain, &init, tag.into(), "register", &key, sender);
+        let param = OwnedParameter::from_serial(&message).expect("Parameter within size bounds");
+        update(chain, sender, "register", param)
+    };
+    let minute = |chain: &mut Chain| {
+        chain
+            .tick_block_time(Duration::from_minutes(1))
+            .expect("Block time does not overflow")
+    };
+
+    let quotas = Quotas {
+        window: Duration::from_days(1),
+        per_provider: Some(3),
+        per_sender: Some(2),
+        cooldown: Duration::from_minutes(1),
+    };
+    let param = OwnedParameter::from_serial(&quotas).expect("Parameter within size bounds");
+    assert_eq!(
+        error(update(&mut chain, BOB, "set_quotas", param.clone())),
+        errors::Error::UnAuthorized
+    );
+    update(&mut chain, ALICE, "set_quotas", param).expect("Setting quotas succeeds");
+    let stored: Quotas = update(&mut chain, ALICE, "get_quotas", OwnedParameter::empty())
+        .expect("View succeeds")
+        .parse_return_value()
+        .expect("Deserialize `Quotas`");
+    assert_eq!(stored, quotas);
+
+    // A sender has to wait between registrations, and may only register two
+    // tags per day.
+    register(&mut chain, ALICE, "alice").expect("Registering succeeds");
+    assert_eq!(
+        error(register(&mut chain, ALICE, "bob")),
+        errors::Error::RegistrationCooldown
+    );
+    minute(&mut chain);
+    register(&mut chain, ALICE, "bob").expect("Registering succeeds");
+    minute(&mut chain);
+    assert_eq!(
+        error(register(&mut chain, ALICE, "carol")),
+        errors::Error::QuotaExceeded
+    );
+
+    // A provider may only register three tags per day, whoever sends them.
+    register(&mut chain, BOB, "carol").expect("Registering succeeds");
+    minute(&mut chain);
+    assert_eq!(
+        error(register(&mut chain, BOB, "dave")),
+        errors::Error::QuotaExceeded
+    );
+
+    // The quotas start over in the next window.
+    chain
+        .tick_block_time(Duration::from_days(1))
+        .expect("Block time does not overflow");
+    register(&mut chain, ALICE, "dave").expect("Registering succeeds");
+}
+
+#[test]
+fn test_register_event_logs_canonical_tag() {
+    let (mut chain, init) = initialize();
+    let signing_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    let public_key = PublicKeyEd25519(signing_key.verifying_key().to_bytes());
+
+    let update = register_tag_with_key(&mut chain, &init, "Buki".into(), &signing_key)
+        .expect("Registering succeeds");
+    let events: Vec<Event> = update
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    let registry = Registry::new(
+        public_key,
+        ContractAddress {
+            index: 0,
+            subindex: 0,
+        },
+        "AfrixLabs".into(),
+    );
+    assert_eq!(
+        events,
+        vec![
+            Event::Register(RegisterEvent {
+                tag: "buki.ccd".into(),
+                public_key: public_key.into(),
+                contract_address: registry.contract_address,
+                provider: registry.provider.clone(),
+                registrar: ALICE_ADDR,
+            }),
+            Event::Mint(MintEvent {
+                token_id: tag_token_id("buki.ccd"),
+                amount: TokenAmountU8(1),
+                owner: ALICE_ADDR,
+            }),
+            Event::TokenMetadata(TokenMetadataEvent {
+                token_id: tag_token_id("buki.ccd"),
+                metadata_url: token_metadata_url("", "buki.ccd"),
+            }),
+        ]
+    );
+
+    // The `Register` event is logged with the tag `57`, while events with the
+    // retired tag `28` still parse in the layout from before secp256k1 keys.
+    let (_, logged) = update.events().next().expect("Registry events");
+    assert_eq!(logged[0].as_ref()[0], 57);
+    let legacy = LegacyRegisterEvent {
+        tag: "buki.ccd".into(),
+        public_key,
+        contract_address: registry.contract_address,
+        provider: registry.provider.clone(),
+        registrar: ALICE_ADDR,
+    };
+    let mut bytes = vec![28];
+    bytes.extend(concordium_std::to_bytes(&legacy));
+    assert_eq!(
+        concordium_std::from_bytes::<Event>(&bytes),
+        Ok(Event::LegacyRegister(legacy))
+    );
+}
+
+#[test]
+fn test_events_fit_at_maximum_lengths() {
+    use ed25519_dalek::Signer;
+
+    let (mut chain, init) = initialize();
+    let signing_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    let public_key = RegistryKey::from(PublicKeyEd25519(signing_key.verifying_key().to_bytes()));
+
+    let update = |chain: &mut Chain, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let register = |chain: &mut Chain, tag: String, provider: String| {
+        let param = RegisterParam {
+            tag,
+            data: Registry::new(
+                PublicKeyEd25519(signing_key.verifying_key().to_bytes()),
+                ContractAddress {
+                    index: u64::MAX,
+                    subindex: u64::MAX,
+                },
+                provider,
+            ),
+            owner: ALICE_ADDR,
+            entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+            submitter: None,
+            expiry_time: chain
+                .block_time()
+                .checked_add(Duration::from_seconds(5))
+                .expect("Expiry does not overflow"),
+        };
+        let param = sign_register_param(chain, &init, param, &signing_key);
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, "register", param)
+    };
+    let set_provider = |chain: &mut Chain, tag: &str, nonce: u64, provider: String| {
+        let message = UpdateParam {
+            tag: tag.into(),
+            nonce,
+            update: RecordUpdate {
+                contract_address: None,
+                provider: Some(provider),
+            },
+            entry_point: OwnedEntrypointName::new_unchecked("update".into()),
+            submitter: None,
+            expiry_time: chain
+                .block_time()
+                .checked_add(Duration::from_seconds(5))
+                .expect("Expiry does not overflow"),
+        };
+        let invoke = chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(
+                        "registry.get_update_hash".to_string(),
+                    ),
+                    message: OwnedParameter::from_serial(&message)
+                        .expect("Parameter within size bounds"),
+                },
+            )
+            .expect("Should be able to query the message hash");
+        let signature = signing_key.sign(&invoke.return_value);
+        let param = UpdateMessage {
+            signer: public_key,
+            signature: SignatureEd25519(signature.to_bytes()).into(),
+            message,
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, "update", param)
+    };
+
+    // The base of the metadata URLs takes at most 100 bytes.
+    let param =
+        OwnedParameter::from_serial(&"u".repeat(101)).expect("Parameter within size bounds");
+    assert_eq!(
+        error(update(&mut chain, "set_metadata_url", param)),
+        errors::Error::MetadataUrlTooLong
+    );
+    let param =
+        OwnedParameter::from_serial(&"u".repeat(100)).expect("Parameter within size bounds");
+    update(&mut chain, "set_metadata_url", param).expect("Setting the URL succeeds");
+
+    // The canonical tag and the provider take at most 128 bytes each.
+    assert_eq!(
+        error(register(&mut chain, "a".repeat(125), "p".repeat(128))),
+        errors::Error::TagTooLong
+    );
+    assert_eq!(
+        error(register(&mut chain, "a".repeat(124), "p".repeat(129))),
+        errors::Error::ProviderTooLong
+    );
+    // At the limits, every event still fits.
+    let tag = format!("{}.ccd", "a".repeat(124));
+    let invoke = register(&mut chain, "a".repeat(124), "p".repeat(128))
+        .expect("Registering at the maximum lengths succeeds");
+    let events: Vec<Event> = invoke
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    assert_eq!(
+        events.last(),
+        Some(&Event::TokenMetadata(TokenMetadataEvent {
+            token_id: tag_token_id(&tag),
+            metadata_url: token_metadata_url(&"u".repeat(100), &tag),
+        }))
+    );
+
+    assert_eq!(
+        error(set_provider(&mut chain, &tag, 0, "q".repeat(129))),
+        errors::Error::ProviderTooLong
+    );
+    set_provider(&mut chain, &tag, 0, "q".repeat(128))
+        .expect("Updating to the maximum provider length succeeds");
+}
+
+#[test]
+fn test_resolve_returns_record_or_none() {
+    let (chain, init, alice_public_key) = initialize_chain_and_create_tag("Alice".into());
+
+    let resolve = |tag: &str| -> Option<TagRecord> {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    address: init.contract_address,
+                    amount: Amount::zero(),
+                    receive_name: OwnedReceiveName::new_unchecked("registry.resolve".to_string()),
+                    message: OwnedParameter::from_serial(&tag)
+                        .expect("Parameter within size bounds"),
+                },
+            )
+            .expect("Resolving never rejects")
+            .parse_return_value()
+            .expect("Deserialize response")
+    };
+
+    let record = resolve("alice").expect("Tag is registered");
+    assert_eq!(record.tag, "alice.ccd");
+    assert_eq!(record.registry.public_key, alice_public_key.into());
+    assert_eq!(record.registrar, ALICE_ADDR);
+    assert_eq!(record.registered_at, record.updated_at);
+    assert!(resolve("bob").is_none());
+}
+
+#[test]
+fn test_get_key_returns_registration_metadata() {
+    let (mut chain, init) = initialize();
+    chain
+        .tick_block_time(Duration::from_millis(1000))
+        .expect("Block time does not overflow");
+    register_tag(&mut chain, &init, "alice".into());
+
+    let update = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                address: init.contract_address,
+                amount: Amount::zero(),
+                receive_name: OwnedReceiveName::new_unchecked("registry.get_key".to_string()),
+                message: OwnedParameter::from_serial(&"alice")
+                    .expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Tag is registered");
+    let entry: RegistryEntry = update.parse_return_value().expect("Deserialize response");
+    assert_eq!(entry.tag, "alice.ccd");
+    assert_eq!(entry.registrar, ALICE_ADDR);
+    assert_eq!(entry.registered_at, Timestamp::from_timestamp_millis(1000));
+    assert_eq!(entry.updated_at, entry.registered_at);
+}
+
+#[test]
+fn test_register_through_permit() {
+    use ed25519_dalek::Signer;
+
+    let (mut chain, init) = initialize();
+    let rng = &mut rand::thread_rng();
+
+    // Bob holds no CCD, Alice sponsors his registration.
+    let bob_account_key = ed25519::SigningKey::generate(rng);
+    chain.create_account(Account::new_with_keys(
+        BOB,
+        AccountBalance {
+            total: Amount::zero(),
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        },
+        AccountAccessStructure::singleton(bob_account_key.verifying_key()),
+    ));
+
+    let tag_key = ed25519::SigningKey::generate(rng);
+    let register_message =
+        signed_register_message(&chain, &init, "bob".into(), "register", &tag_key, BOB);
+    let mut param = PermitParam {
+        signature: AccountSignatures {
+            sigs: BTreeMap::new(),
+        },
+        signer: BOB,
+        message: PermitMessage {
+            contract_address: init.contract_address,
+            nonce: 0,
+            timestamp: Timestamp::from_timestamp_millis(5000),
+            entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+            payload: to_bytes(&register_message),
+        },
+    };
+
+    // Get the message hash to be signed.
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "registry.viewMessageHash".to_string(),
+                ),
+                message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query viewMessageHash");
+    let signature = bob_account_key.sign(&invoke.return_value);
+    param.signature = AccountSignatures {
+        sigs: BTreeMap::from([(
+            0,
+            CredentialSignatures {
+                sigs: BTreeMap::from([(
+                    0,
+                    Signature::Ed25519(SignatureEd25519(signature.to_bytes())),
+                )]),
+            },
+        )]),
+    };
+
+    let payload = UpdateContractPayload {
+        address: init.contract_address,
+        amount: Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("registry.permit".to_string()),
+        message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+    };
+    let update = chain
+        .contract_update(
+            SIGNER,
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            payload.clone(),
+        )
+        .expect("Permit succeeds");
+    let events: Vec<Event> = update
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    assert!(matches!(&events[..], [
+        Event::Register(RegisterEvent { registrar, .. }),
+        Event::Mint(_),
+        Event::TokenMetadata(_),
+        Event::Nonce(NonceEvent { account: BOB, nonce: 0 }),
+    ] if *registrar == Address::Account(BOB)));
+
+    // The message can not be replayed.
     let update = chain
         .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
-        .expect_err("failed to update contract");
+        .expect_err("Replaying the permit fails");
+    let error: errors::Error = update.parse_return_value().expect("Deserialize `Error`");
+    assert_eq!(error, errors::Error::NonceMismatch);
+
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("registry.nonceOf".to_string()),
+                message: OwnedParameter::from_serial(&VecOfAccountAddresses {
+                    queries: vec![BOB, ALICE],
+                })
+                .expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query nonceOf");
+    let nonces: NonceOfQueryResponse = invoke.parse_return_value().expect("Deserialize response");
+    assert_eq!(nonces.0, vec![1, 0]);
+
+    // Entry points outside the supported subset are rejected.
+    param.message.nonce = 1;
+    param.message.entry_point = OwnedEntrypointName::new_unchecked("update".into());
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "registry.viewMessageHash".to_string(),
+                ),
+                message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query viewMessageHash");
+    let signature = bob_account_key.sign(&invoke.return_value);
+    param.signature = AccountSignatures {
+        sigs: BTreeMap::from([(
+            0,
+            CredentialSignatures {
+                sigs: BTreeMap::from([(
+                    0,
+                    Signature::Ed25519(SignatureEd25519(signature.to_bytes())),
+                )]),
+            },
+        )]),
+    };
+    let payload = UpdateContractPayload {
+        address: init.contract_address,
+        amount: Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("registry.permit".to_string()),
+        message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+    };
+    let error: errors::Error = chain
+        .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+        .expect_err("Permitting `update` fails")
+        .parse_return_value()
+        .expect("Deserialize `Error`");
+    assert_eq!(error, errors::Error::UnsupportedPermitEntryPoint);
+}
+
+#[test]
+fn test_permit_checks_submitter_against_signer() {
+    let (mut chain, init) = initialize();
+    let rng = &mut rand::thread_rng();
+
+    // Bob signs the permit messages, Alice sponsors them.
+    let bob_account_key = ed25519::SigningKey::generate(rng);
+    chain.create_account(Account::new_with_keys(
+        BOB,
+        AccountBalance {
+            total: Amount::zero(),
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        },
+        AccountAccessStructure::singleton(bob_account_key.verifying_key()),
+    ));
+    let tag_key = ed25519::SigningKey::generate(rng);
+    let permit = |chain: &mut Chain, submitter: Address| {
+        let mut register_message =
+            signed_register_message(chain, &init, "bob".into(), "register", &tag_key, BOB);
+        register_message.message.submitter = Some(submitter);
+        let register_message =
+            sign_register_param(chain, &init, register_message.message, &tag_key);
+        let param = signed_permit(
+            chain,
+            &init,
+            BOB,
+            &bob_account_key,
+            0,
+            "register",
+            to_bytes(&register_message),
+        );
+        chain.contract_update(
+            SIGNER,
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                address: init.contract_address,
+                amount: Amount::zero(),
+                receive_name: OwnedReceiveName::new_unchecked("registry.permit".to_string()),
+                message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+            },
+        )
+    };
+
+    // A message bound to the sponsor is rejected, it is submitted by Bob.
+    let error: errors::Error = permit(&mut chain, ALICE_ADDR)
+        .expect_err("Permit with the sponsor as submitter fails")
+        .parse_return_value()
+        .expect("Deserialize `Error`");
+    assert_eq!(error, errors::Error::UnAuthorized);
+
+    // A message bound to the signer of the permit message goes through.
+    let update = permit(&mut chain, Address::Account(BOB)).expect("Permit succeeds");
+    let events: Vec<Event> = update
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    assert!(
+        matches!(&events[0], Event::Register(RegisterEvent { registrar, .. })
+        if *registrar == Address::Account(BOB))
+    );
+}
+
+#[test]
+fn test_register_with_secp256k1_key() {
+    let (mut chain, init) = initialize();
+
+    let secp = secp256k1::Secp256k1::new();
+    let (secret_key, public_key) = secp.generate_keypair(&mut rand::thread_rng());
+    let public_key = PublicKeyEcdsaSecp256k1(public_key.serialize());
+    let param = RegisterParam {
+        tag: "eve".into(),
+        data: Registry::new(
+            public_key,
+            ContractAddress {
+                index: 0,
+                subindex: 0,
+            },
+            "AfrixLabs".into(),
+        ),
+        owner: Address::Account(ALICE),
+        entry_point: OwnedEntrypointName::new_unchecked("register".into()),
+        submitter: None,
+        expiry_time: Timestamp::from_timestamp_millis(5000),
+    };
+    // Get the message hash to be signed.
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "registry.get_param_hash".to_string(),
+                ),
+                message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query get_param_hash");
+    let message_hash: [u8; 32] = invoke.parse_return_value().expect("Deserialize hash");
+    let signature = secp.sign_ecdsa(&secp256k1::Message::from_digest(message_hash), &secret_key);
+
+    let message = RegisterMessage {
+        signer: public_key.into(),
+        signature: SignatureEcdsaSecp256k1(signature.serialize_compact()).into(),
+        message: param,
+    };
+    let payload = UpdateContractPayload {
+        address: init.contract_address,
+        amount: Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("registry.register".to_string()),
+        message: OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+    };
+    chain
+        .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+        .expect("Registering with a secp256k1 key succeeds");
+
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("registry.get_tag".to_string()),
+                message: OwnedParameter::from_serial(&RegistryKey::from(public_key))
+                    .expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Key is registered");
+    let tag: String = invoke.parse_return_value().expect("Deserialize tag");
+    assert_eq!(tag, "eve.ccd");
+}
+
+#[test]
+fn test_commit_and_reveal() {
+    let (mut chain, init) = initialize();
+    let signing_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    let public_key = PublicKeyEd25519(signing_key.verifying_key().to_bytes());
+    let salt = [7u8; 32];
+
+    let commit = |chain: &mut Chain, tag: &str| {
+        let preimage = CommitmentPreimage {
+            tag: tag.into(),
+            public_key: public_key.into(),
+            salt,
+        };
+        let invoke = chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(
+                        "registry.get_commitment_hash".to_string(),
+                    ),
+                    message: OwnedParameter::from_serial(&preimage)
+                        .expect("Parameter within size bounds"),
+                },
+            )
+            .expect("Should be able to query get_commitment_hash");
+        let commitment: [u8; 32] = invoke.parse_return_value().expect("Deserialize hash");
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked("registry.commit".to_string()),
+            message: OwnedParameter::from_serial(&commitment)
+                .expect("Parameter within size bounds"),
+        };
+        chain
+            .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+            .expect("Committing succeeds");
+    };
+    let reveal = |chain: &mut Chain, tag: &str| {
+        let param = RevealParam {
+            message: signed_register_message(
+                chain,
+                &init,
+                tag.into(),
+                "reveal",
+                &signing_key,
+                ALICE,
+            ),
+            salt,
+        };
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked("registry.reveal".to_string()),
+            message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+        };
+        chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+    };
+    let reveal_error = |chain: &mut Chain, tag: &str| -> errors::Error {
+        reveal(chain, tag)
+            .expect_err("Revealing fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+
+    commit(&mut chain, "Alice");
+    commit(&mut chain, "alice_backup");
+    assert_eq!(
+        reveal_error(&mut chain, "alice"),
+        errors::Error::CommitmentTooNew
+    );
+    assert_eq!(
+        reveal_error(&mut chain, "bob"),
+        errors::Error::CommitmentDoesNotExist
+    );
+
+    chain
+        .tick_block_time(Duration::from_minutes(1))
+        .expect("Block time does not overflow");
+    reveal(&mut chain, "alice").expect("Revealing succeeds");
+    // The commitment is consumed by the reveal.
+    assert_eq!(
+        reveal_error(&mut chain, "alice"),
+        errors::Error::CommitmentDoesNotExist
+    );
+
+    chain
+        .tick_block_time(Duration::from_days(1))
+        .expect("Block time does not overflow");
+    assert_eq!(
+        reveal_error(&mut chain, "alice_backup"),
+        errors::Error::CommitmentExpired
+    );
+}
+
+/// Test that premium tags can only be won in an auction, that outbid bids are
+/// refunded and that settling registers the tag to the winner.
+#[test]
+fn test_auction_premium_tag() {
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new(CAROL, ACC_INITIAL_BALANCE));
+    let bob_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    let carol_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+
+    let update = |chain: &mut Chain,
+                  sender: AccountAddress,
+                  entrypoint: &str,
+                  amount: Amount,
+                  message: OwnedParameter| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount,
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let bid = |chain: &mut Chain,
+               sender: AccountAddress,
+               signing_key: &ed25519::SigningKey,
+               amount: Amount| {
+        let message =
+            signed_register_message(chain, &init, "Ace".into(), "bid", signing_key, sender);
+        let param = OwnedParameter::from_serial(&message).expect("Parameter within size bounds");
+        update(chain, sender, "bid", amount, param)
+    };
+    let settle = |chain: &mut Chain| {
+        let param =
+            OwnedParameter::from_serial(&"ace".to_string()).expect("Parameter within size bounds");
+        update(chain, BOB, "settle_auction", Amount::zero(), param)
+    };
+
+    let premium = SetPremiumTagsParam {
+        tags: vec!["Ace".into()],
+        premium: true,
+    };
+    let param = OwnedParameter::from_serial(&premium).expect("Parameter within size bounds");
+    assert_eq!(
+        error(update(
+            &mut chain,
+            BOB,
+            "set_premium_tags",
+            Amount::zero(),
+            param.clone()
+        )),
+        errors::Error::UnAuthorized
+    );
+    update(&mut chain, ALICE, "set_premium_tags", Amount::zero(), param)
+        .expect("Admin can set premium tags");
+    assert_eq!(
+        error(register_tag_with_key(
+            &mut chain,
+            &init,
+            "ace".into(),
+            &bob_key
+        )),
+        errors::Error::PremiumTag
+    );
+
+    let start = StartAuctionParam {
+        tag: "ace".into(),
+        end: chain
+            .block_time()
+            .checked_add(Duration::from_hours(1))
+            .expect("End does not overflow"),
+        reserve_price: Amount::from_ccd(100),
+    };
+    let param = OwnedParameter::from_serial(&start).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "start_auction", Amount::zero(), param)
+        .expect("Admin can start an auction");
+
+    assert_eq!(
+        error(bid(&mut chain, BOB, &bob_key, Amount::from_ccd(50))),
+        errors::Error::BidTooLow
+    );
+    bid(&mut chain, BOB, &bob_key, Amount::from_ccd(100)).expect("Bid meets the reserve price");
+    assert_eq!(
+        error(bid(&mut chain, CAROL, &carol_key, Amount::from_ccd(100))),
+        errors::Error::BidTooLow
+    );
+    let outbid = bid(&mut chain, CAROL, &carol_key, Amount::from_ccd(150))
+        .expect("Bid above the highest bid");
+    assert_eq!(
+        outbid.account_transfers().collect::<Vec<_>>(),
+        [(init.contract_address, Amount::from_ccd(100), BOB)]
+    );
+
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("registry.get_auction".to_string()),
+                message: OwnedParameter::from_serial(&"ace".to_string())
+                    .expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query get_auction");
+    let auction: Option<Auction> = invoke.parse_return_value().expect("Deserialize auction");
+    let highest_bid = auction
+        .and_then(|auction| auction.highest_bid)
+        .expect("Auction has a bid");
+    assert_eq!(highest_bid.bidder, CAROL);
+    assert_eq!(highest_bid.amount, Amount::from_ccd(150));
+
+    assert_eq!(error(settle(&mut chain)), errors::Error::AuctionNotEnded);
+    chain
+        .tick_block_time(Duration::from_hours(1))
+        .expect("Block time does not overflow");
+    assert_eq!(
+        error(bid(&mut chain, BOB, &bob_key, Amount::from_ccd(200))),
+        errors::Error::AuctionEnded
+    );
+    let settled = settle(&mut chain).expect("Settling succeeds");
+    assert_eq!(
+        settled.account_transfers().collect::<Vec<_>>(),
+        [(init.contract_address, Amount::from_ccd(150), ALICE)]
+    );
+    assert_eq!(
+        error(settle(&mut chain)),
+        errors::Error::AuctionDoesNotExist
+    );
+
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("registry.resolve".to_string()),
+                message: OwnedParameter::from_serial(&"ace".to_string())
+                    .expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query resolve");
+    let record: Option<TagRecord> = invoke.parse_return_value().expect("Deserialize record");
+    let record = record.expect("Tag is registered");
+    assert_eq!(record.registrar, Address::Account(CAROL));
+    assert_eq!(
+        record.registry.public_key,
+        RegistryKey::from(PublicKeyEd25519(carol_key.verifying_key().to_bytes()))
+    );
+}
+
+/// Test that registered tags are CIS-2 tokens that can be transferred by their
+/// owner and its operators.
+#[test]
+fn test_tags_are_cis2_tokens() {
+    let (mut chain, init, public_key) = initialize_chain_and_create_tag("alice".into());
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    let token_id = tag_token_id("alice.ccd");
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let bob_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    // Transfers the tag to BOB, with a registration of the tag for the key of
+    // BOB, signed for the given entry point and owner.
+    let transfer = |chain: &mut Chain,
+                    sender: AccountAddress,
+                    from: AccountAddress,
+                    registration: Option<(&str, AccountAddress)>| {
+        let data = registration.map_or_else(AdditionalData::empty, |(entry_point, owner)| {
+            let message =
+                signed_register_message(chain, &init, "alice".into(), entry_point, &bob_key, owner);
+            AdditionalData::from(to_bytes(&message))
+        });
+        let param = TransferParams(vec![concordium_cis2::Transfer {
+            token_id: token_id.clone(),
+            amount: TokenAmountU8(1),
+            from: Address::Account(from),
+            to: Receiver::Account(BOB),
+            data,
+        }]);
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, sender, "transfer", param)
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> ContractError {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `ContractError`")
+    };
+    let view = |chain: &Chain, entrypoint: &str, message: OwnedParameter| {
+        chain.contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+                message,
+            },
+        )
+    };
+    let balances = |chain: &Chain| -> Vec<TokenAmountU8> {
+        let param = BalanceOfQueryParams {
+            queries: vec![ALICE, BOB]
+                .into_iter()
+                .map(|account| BalanceOfQuery {
+                    token_id: token_id.clone(),
+                    address: Address::Account(account),
+                })
+                .collect(),
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        let invoke = view(chain, "balanceOf", param).expect("Should be able to query balanceOf");
+        let response: BalanceOfQueryResponse<TokenAmountU8> =
+            invoke.parse_return_value().expect("Deserialize balances");
+        response.0
+    };
+
+    assert_eq!(balances(&chain), [TokenAmountU8(1), TokenAmountU8(0)]);
+    assert_eq!(
+        error(transfer(&mut chain, BOB, ALICE, Some(("transfer", BOB)))),
+        ContractError::Unauthorized
+    );
+
+    let param = UpdateOperatorParams(vec![UpdateOperator {
+        update: OperatorUpdate::Add,
+        operator: Address::Account(BOB),
+    }]);
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "updateOperator", param).expect("Adding an operator succeeds");
+    let param = OperatorOfQueryParams {
+        queries: vec![OperatorOfQuery {
+            owner: ALICE_ADDR,
+            address: Address::Account(BOB),
+        }],
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    let invoke = view(&chain, "operatorOf", param).expect("Should be able to query operatorOf");
+    let response: OperatorOfQueryResponse =
+        invoke.parse_return_value().expect("Deserialize operators");
+    assert_eq!(response.0, [true]);
+
+    // The tag only changes hands together with a registration of the key of
+    // the receiver, signed for `transfer` and naming the receiver as owner.
+    assert_eq!(
+        error(transfer(&mut chain, BOB, ALICE, None)),
+        ContractError::Custom(errors::Error::ParseParams)
+    );
+    assert_eq!(
+        error(transfer(&mut chain, BOB, ALICE, Some(("register", BOB)))),
+        ContractError::Custom(errors::Error::WrongEntryPoint)
+    );
+    assert_eq!(
+        error(transfer(&mut chain, BOB, ALICE, Some(("transfer", ALICE)))),
+        ContractError::Custom(errors::Error::OwnerMismatch)
+    );
+
+    let invoke =
+        transfer(&mut chain, BOB, ALICE, Some(("transfer", BOB))).expect("Operator can transfer");
+    let events: Vec<Event> = invoke
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    let registry = Registry::new(
+        PublicKeyEd25519(bob_key.verifying_key().to_bytes()),
+        ContractAddress {
+            index: 0,
+            subindex: 0,
+        },
+        "AfrixLabs".into(),
+    );
+    assert_eq!(
+        events,
+        [
+            Event::Transfer(TransferEvent {
+                token_id: token_id.clone(),
+                amount: TokenAmountU8(1),
+                from: ALICE_ADDR,
+                to: Address::Account(BOB),
+            }),
+            Event::Update(UpdateEvent {
+                tag: "alice.ccd".into(),
+                registry: registry.clone(),
+                updated_by: Address::Account(BOB),
+            }),
+            Event::TokenMetadata(TokenMetadataEvent {
+                token_id: token_id.clone(),
+                metadata_url: token_metadata_url("", "alice.ccd"),
+            }),
+        ]
+    );
+    assert_eq!(balances(&chain), [TokenAmountU8(0), TokenAmountU8(1)]);
+    assert_eq!(
+        error(transfer(&mut chain, ALICE, ALICE, Some(("transfer", BOB)))),
+        ContractError::InsufficientFunds
+    );
+
+    // The tag resolves to the key of the receiver.
+    let param =
+        OwnedParameter::from_serial(&"alice".to_string()).expect("Parameter within size bounds");
+    let invoke = view(&chain, "resolve", param).expect("Should be able to query resolve");
+    let record: Option<TagRecord> = invoke.parse_return_value().expect("Deserialize record");
+    let record = record.expect("Tag is registered");
+    assert_eq!(record.owner, Address::Account(BOB));
+    assert_eq!(record.registrar, ALICE_ADDR);
+    assert_eq!(record.registry, registry);
+    assert_ne!(record.registry.public_key, RegistryKey::from(public_key));
+
+    // The metadata is served under the URL set by the admin, followed by the
+    // token id in hex.
+    let param = OwnedParameter::from_serial(&"https://example.com/metadata/".to_string())
+        .expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "set_metadata_url", param).expect("Admin can set the URL");
+    let param = TokenMetadataQueryParams {
+        queries: vec![token_id.clone()],
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    let invoke = view(&chain, "tokenMetadata", param).expect("Should be able to query metadata");
+    let response: TokenMetadataQueryResponse =
+        invoke.parse_return_value().expect("Deserialize metadata");
+    assert_eq!(
+        response.0,
+        [MetadataUrl {
+            url: "https://example.com/metadata/616c6963652e636364".into(),
+            hash: None,
+        }]
+    );
+    let json = token_metadata_json("alice.ccd", &record.registry);
+    assert!(json.starts_with("{\"name\":\"alice.ccd\",\"unique\":true,"));
+
+    let param = TokenMetadataQueryParams {
+        queries: vec![tag_token_id("bob.ccd")],
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    let error: ContractError = view(&chain, "tokenMetadata", param)
+        .expect_err("Unknown tokens are rejected")
+        .parse_return_value()
+        .expect("Deserialize `ContractError`");
+    assert_eq!(error, ContractError::InvalidTokenId);
+
+    let param = SupportsQueryParams {
+        queries: vec![StandardIdentifierOwned::new_unchecked("CIS-2".into())],
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    let invoke = view(&chain, "supports", param).expect("Should be able to query supports");
+    let response: SupportsQueryResponse =
+        invoke.parse_return_value().expect("Deserialize supports");
+    assert!(matches!(response.results[..], [SupportResult::Support]));
+}
+
+/// Test that a tag can be bought through a sale offer signed by its holder,
+/// which pays the seller and the protocol fee and rebinds the tag to the key of
+/// the buyer.
+#[test]
+fn test_buy_tag_through_sale_offer() {
+    use ed25519_dalek::Signer;
+
+    let (mut chain, init) = initialize();
+    let rng = &mut rand::thread_rng();
+    let bob_account_key = ed25519::SigningKey::generate(rng);
+    chain.create_account(Account::new_with_keys(
+        BOB,
+        AccountBalance {
+            total: ACC_INITIAL_BALANCE,
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        },
+        AccountAccessStructure::singleton(bob_account_key.verifying_key()),
+    ));
+    chain.create_account(Account::new(CAROL, ACC_INITIAL_BALANCE));
+
+    let update = |chain: &mut Chain,
+                  sender: AccountAddress,
+                  entrypoint: &str,
+                  amount: Amount,
+                  message: OwnedParameter| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount,
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+
+    // Bob registers the tag and puts it up for sale to Carol.
+    let bob_key = ed25519::SigningKey::generate(rng);
+    let message = signed_register_message(&chain, &init, "bob".into(), "register", &bob_key, BOB);
+    let param = OwnedParameter::from_serial(&message).expect("Parameter within size bounds");
+    update(&mut chain, BOB, "register", Amount::zero(), param).expect("Registering succeeds");
+    let param = OwnedParameter::from_serial(&250u16).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "set_protocol_fee", Amount::zero(), param)
+        .expect("Admin can set the protocol fee");
+
+    let offer = SaleOffer {
+        contract_address: init.contract_address,
+        seller: BOB,
+        nonce: 0,
+        tag: "bob".into(),
+        tag_nonce: 0,
+        price: Amount::from_ccd(100),
+        buyer: Some(CAROL),
+        entry_point: OwnedEntrypointName::new_unchecked("list_sale".into()),
+        expiry_time: chain
+            .block_time()
+            .checked_add(Duration::from_days(2))
+            .expect("Expiry does not overflow"),
+    };
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "registry.get_offer_hash".to_string(),
+                ),
+                message: OwnedParameter::from_serial(&offer).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query get_offer_hash");
+    let signature = bob_account_key.sign(&invoke.return_value);
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "registry.get_offer_key_hash".to_string(),
+                ),
+                message: OwnedParameter::from_serial(&offer).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query get_offer_key_hash");
+    let offer_key_hash: [u8; 32] = invoke.parse_return_value().expect("Deserialize hash");
+    let carol_key = ed25519::SigningKey::generate(rng);
+    let mut param = BuyParam {
+        signature: AccountSignatures {
+            sigs: BTreeMap::from([(
+                0,
+                CredentialSignatures {
+                    sigs: BTreeMap::from([(
+                        0,
+                        Signature::Ed25519(SignatureEd25519(signature.to_bytes())),
+                    )]),
+                },
+            )]),
+        },
+        offer: offer.clone(),
+        message: signed_register_message(&chain, &init, "bob".into(), "buy", &carol_key, CAROL),
+    };
+
+    // The offer must be listed by the key of the tag first.
+    assert_eq!(
+        error(update(
+            &mut chain,
+            CAROL,
+            "buy",
+            Amount::from_ccd(100),
+            OwnedParameter::from_serial(&param).expect("Parameter within size bounds")
+        )),
+        errors::Error::OfferNotListed
+    );
+    // Holding the tag token is not enough to list it.
+    let mut listing = ListSaleMessage {
+        signer: RegistryKey::from(PublicKeyEd25519(bob_key.verifying_key().to_bytes())),
+        signature: SignatureEd25519(bob_account_key.sign(&offer_key_hash).to_bytes()).into(),
+        message: offer,
+    };
+    assert_eq!(
+        error(update(
+            &mut chain,
+            BOB,
+            "list_sale",
+            Amount::zero(),
+            OwnedParameter::from_serial(&listing).expect("Parameter within size bounds")
+        )),
+        errors::Error::WrongSignature
+    );
+    listing.signature = SignatureEd25519(bob_key.sign(&offer_key_hash).to_bytes()).into();
+    let listed = update(
+        &mut chain,
+        BOB,
+        "list_sale",
+        Amount::zero(),
+        OwnedParameter::from_serial(&listing).expect("Parameter within size bounds"),
+    )
+    .expect("Listing succeeds");
+    let events: Vec<Event> = listed
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    let [Event::ChangeQueued(ChangeQueuedEvent { change, .. })] = &events[..] else {
+        panic!("Unexpected events {:?}", events);
+    };
+    assert_eq!(change.change, Change::Sale(offer_key_hash));
+
+    // The listing waits for the change delay, and only `buy` executes it.
+    assert_eq!(
+        error(update(
+            &mut chain,
+            CAROL,
+            "buy",
+            Amount::from_ccd(100),
+            OwnedParameter::from_serial(&param).expect("Parameter within size bounds")
+        )),
+        errors::Error::ChangeNotReady
+    );
+    chain
+        .tick_block_time(Duration::from_days(1))
+        .expect("Block time does not overflow");
+    assert_eq!(
+        error(update(
+            &mut chain,
+            CAROL,
+            "execute_pending",
+            Amount::zero(),
+            OwnedParameter::from_serial(&PendingChangeId {
+                tag: "bob".into(),
+                id: change.id,
+            })
+            .expect("Parameter within size bounds")
+        )),
+        errors::Error::ListingNotExecutable
+    );
+    param.message = signed_register_message(&chain, &init, "bob".into(), "buy", &carol_key, CAROL);
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+
+    assert_eq!(
+        error(update(
+            &mut chain,
+            ALICE,
+            "buy",
+            Amount::from_ccd(100),
+            param.clone()
+        )),
+        errors::Error::UnAuthorized
+    );
+    assert_eq!(
+        error(update(
+            &mut chain,
+            CAROL,
+            "buy",
+            Amount::from_ccd(50),
+            param.clone()
+        )),
+        errors::Error::WrongAmount
+    );
+    let bought = update(
+        &mut chain,
+        CAROL,
+        "buy",
+        Amount::from_ccd(100),
+        param.clone(),
+    )
+    .expect("Buying succeeds");
+    let events: Vec<Event> = bought
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    assert!(matches!(
+        &events[..],
+        [
+            Event::Sale(_),
+            Event::Transfer(TransferEvent {
+                to: Address::Account(CAROL),
+                ..
+            }),
+            Event::TokenMetadata(_),
+            Event::Nonce(NonceEvent {
+                account: BOB,
+                nonce: 0
+            }),
+        ]
+    ));
+    assert_eq!(
+        bought.account_transfers().collect::<Vec<_>>(),
+        [
+            (
+                init.contract_address,
+                Amount::from_micro_ccd(2_500_000),
+                ALICE
+            ),
+            (
+                init.contract_address,
+                Amount::from_micro_ccd(97_500_000),
+                BOB
+            ),
+        ]
+    );
+    // The offer can not be replayed.
+    assert_eq!(
+        error(update(
+            &mut chain,
+            CAROL,
+            "buy",
+            Amount::from_ccd(100),
+            param
+        )),
+        errors::Error::NonceMismatch
+    );
+
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("registry.resolve".to_string()),
+                message: OwnedParameter::from_serial(&"bob".to_string())
+                    .expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query resolve");
+    let record: Option<TagRecord> = invoke.parse_return_value().expect("Deserialize record");
+    let record = record.expect("Tag is registered");
+    assert_eq!(record.owner, Address::Account(CAROL));
+    assert_eq!(
+        record.registry.public_key,
+        RegistryKey::from(PublicKeyEd25519(carol_key.verifying_key().to_bytes()))
+    );
+    assert_eq!(get_stats(&chain, &init).sales, 1);
+
+    // The key of the seller is free again.
+    let key = RegistryKey::from(PublicKeyEd25519(bob_key.verifying_key().to_bytes()));
+    let error: errors::Error = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("registry.get_tag".to_string()),
+                message: OwnedParameter::from_serial(&key).expect("Parameter within size bounds"),
+            },
+        )
+        .expect_err("The key has no tag")
+        .parse_return_value()
+        .expect("Deserialize `Error`");
+    assert_eq!(error, errors::Error::KeyDoesNotExist);
+}
+
+/// Test that the key of a tag can update its record and authorize an operator
+/// to update it, within the scopes and time it was authorized for.
+#[test]
+fn test_tag_operator_updates_record() {
+    use ed25519_dalek::Signer;
+
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    let signing_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    let public_key = RegistryKey::from(PublicKeyEd25519(signing_key.verifying_key().to_bytes()));
+    register_tag_with_key(&mut chain, &init, "alice".into(), &signing_key)
+        .expect("Registering succeeds");
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    // Signs a message with the key of the tag, using the given hash view.
+    let sign = |chain: &Chain, entrypoint: &str, message: OwnedParameter| {
+        let invoke = chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+                    message,
+                },
+            )
+            .expect("Should be able to query the message hash");
+        let signature = signing_key.sign(&invoke.return_value);
+        RegistrySignature::from(SignatureEd25519(signature.to_bytes()))
+    };
+    let operator_update = |chain: &mut Chain, update_record: RecordUpdate| {
+        let param = OperatorUpdateParam {
+            tag: "alice".into(),
+            update: update_record,
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, BOB, "operator_update", param)
+    };
+    let record = |chain: &Chain| -> RegistryEntry {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    address: init.contract_address,
+                    amount: Amount::zero(),
+                    receive_name: OwnedReceiveName::new_unchecked("registry.get_key".to_string()),
+                    message: OwnedParameter::from_serial(&"alice")
+                        .expect("Parameter within size bounds"),
+                },
+            )
+            .expect("Tag is registered")
+            .parse_return_value()
+            .expect("Deserialize response")
+    };
+    let new_contract = ContractAddress {
+        index: 5,
+        subindex: 0,
+    };
+
+    assert_eq!(
+        error(operator_update(
+            &mut chain,
+            RecordUpdate {
+                contract_address: Some(new_contract),
+                provider: None,
+            }
+        )),
+        errors::Error::UnAuthorized
+    );
+
+    let message = TagOperatorParam {
+        tag: "alice".into(),
+        nonce: 0,
+        operator: Address::Account(BOB),
+        authorization: Some(OperatorAuthorization {
+            scopes: vec![UpdateScope::ContractAddress],
+            until: Some(
+                chain
+                    .block_time()
+                    .checked_add(Duration::from_days(1))
+                    .expect("Time does not overflow"),
+            ),
+        }),
+        entry_point: OwnedEntrypointName::new_unchecked("set_tag_operator".into()),
+        submitter: None,
+        expiry_time: chain
+            .block_time()
+            .checked_add(Duration::from_seconds(5))
+            .expect("Expiry does not overflow"),
+    };
+    let signature = sign(
+        &chain,
+        "get_tag_operator_hash",
+        OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+    );
+    let param = TagOperatorMessage {
+        signer: public_key,
+        signature,
+        message,
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "set_tag_operator", param.clone())
+        .expect("Authorizing the operator succeeds");
+    // The message can not be replayed.
+    assert_eq!(
+        error(update(&mut chain, ALICE, "set_tag_operator", param)),
+        errors::Error::NonceMismatch
+    );
+
+    operator_update(
+        &mut chain,
+        RecordUpdate {
+            contract_address: Some(new_contract),
+            provider: None,
+        },
+    )
+    .expect("Operator can update the contract address");
+    // The new contract address only takes effect after the change delay.
+    assert_ne!(record(&chain).registry.contract_address, new_contract);
+    chain
+        .tick_block_time(Duration::from_days(1))
+        .expect("Block time does not overflow");
+    let param = PendingChangeId {
+        tag: "alice".into(),
+        id: 0,
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "execute_pending", param).expect("Executing succeeds");
+    assert_eq!(record(&chain).registry.contract_address, new_contract);
+    assert_eq!(
+        error(operator_update(
+            &mut chain,
+            RecordUpdate {
+                contract_address: None,
+                provider: Some("OtherLabs".into()),
+            }
+        )),
+        errors::Error::UnAuthorized
+    );
+
+    // The key itself can update every part of the record.
+    let message = UpdateParam {
+        tag: "alice".into(),
+        nonce: 1,
+        update: RecordUpdate {
+            contract_address: None,
+            provider: Some("OtherLabs".into()),
+        },
+        entry_point: OwnedEntrypointName::new_unchecked("update".into()),
+        submitter: None,
+        expiry_time: chain
+            .block_time()
+            .checked_add(Duration::from_seconds(5))
+            .expect("Expiry does not overflow"),
+    };
+    let signature = sign(
+        &chain,
+        "get_update_hash",
+        OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+    );
+    let param = UpdateMessage {
+        signer: public_key,
+        signature,
+        message,
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "update", param).expect("Key can update the provider");
+    let entry = record(&chain);
+    assert_eq!(entry.registry.provider, "OtherLabs");
+    assert_eq!(entry.nonce, 2);
+
+    // The authorization of the operator expires.
+    chain
+        .tick_block_time(Duration::from_days(2))
+        .expect("Block time does not overflow");
+    assert_eq!(
+        error(operator_update(
+            &mut chain,
+            RecordUpdate {
+                contract_address: Some(new_contract),
+                provider: None,
+            }
+        )),
+        errors::Error::UnAuthorized
+    );
+}
+
+/// Test that guardians can jointly recover a tag to a new key after the
+/// recovery window, and that the current key can cancel the recovery.
+#[test]
+fn test_guardians_recover_tag() {
+    use ed25519_dalek::Signer;
+
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new(CAROL, ACC_INITIAL_BALANCE));
+    let rng = &mut rand::thread_rng();
+    let tag_key = ed25519::SigningKey::generate(rng);
+    let guardian_key = ed25519::SigningKey::generate(rng);
+    let new_key = ed25519::SigningKey::generate(rng);
+    let registry_key = |key: &ed25519::SigningKey| {
+        RegistryKey::from(PublicKeyEd25519(key.verifying_key().to_bytes()))
+    };
+    register_tag_with_key(&mut chain, &init, "alice".into(), &tag_key)
+        .expect("Registering succeeds");
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let view = |chain: &Chain, entrypoint: &str, message: OwnedParameter| {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+                    message,
+                },
+            )
+            .expect("Should be able to query the view")
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    // Signs a message with `key`, using the given hash view.
+    let sign = |chain: &Chain, key: &ed25519::SigningKey, entrypoint: &str, message| {
+        let hash = view(chain, entrypoint, message).return_value;
+        RegistrySignature::from(SignatureEd25519(key.sign(&hash).to_bytes()))
+    };
+    let expiry_time = |chain: &Chain| {
+        chain
+            .block_time()
+            .checked_add(Duration::from_seconds(5))
+            .expect("Expiry does not overflow")
+    };
+    let approve = |chain: &mut Chain, sender: AccountAddress, with_guardian_key: bool, nonce| {
+        let message = RecoveryParam {
+            tag: "alice".into(),
+            new_key: registry_key(&new_key),
+            nonce,
+            entry_point: OwnedEntrypointName::new_unchecked("approve_recovery".into()),
+            submitter: None,
+            expiry_time: expiry_time(chain),
+        };
+        let mut signatures = Vec::new();
+        if with_guardian_key {
+            let param =
+                OwnedParameter::from_serial(&message).expect("Parameter within size bounds");
+            let signature = sign(chain, &guardian_key, "get_recovery_hash", param);
+            signatures.push((registry_key(&guardian_key), signature));
+        }
+        let param = ApproveRecoveryParam {
+            message,
+            signatures,
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, sender, "approve_recovery", param)
+    };
+    let finalize = |chain: &mut Chain| {
+        let param = OwnedParameter::from_serial(&"alice".to_string())
+            .expect("Parameter within size bounds");
+        update(chain, CAROL, "finalize_recovery", param)
+    };
+    let recovery = |chain: &Chain| -> Option<Recovery> {
+        let param = OwnedParameter::from_serial(&"alice".to_string())
+            .expect("Parameter within size bounds");
+        view(chain, "get_recovery", param)
+            .parse_return_value()
+            .expect("Deserialize recovery")
+    };
+
+    // The key appoints Bob and a guardian key, both of which must approve.
+    let config = GuardianConfig {
+        guardians: vec![
+            Guardian::Address(Address::Account(BOB)),
+            Guardian::Key(registry_key(&guardian_key)),
+        ],
+        threshold: 2,
+    };
+    let set_guardians = |chain: &mut Chain, config: &GuardianConfig, nonce| {
+        let message = GuardiansParam {
+            tag: "alice".into(),
+            nonce,
+            config: Some(config.clone()),
+            entry_point: OwnedEntrypointName::new_unchecked("set_guardians".into()),
+            submitter: None,
+            expiry_time: expiry_time(chain),
+        };
+        let signature = sign(
+            chain,
+            &tag_key,
+            "get_guardians_hash",
+            OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+        );
+        let param = GuardiansMessage {
+            signer: registry_key(&tag_key),
+            signature,
+            message,
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, ALICE, "set_guardians", param).expect("Setting guardians succeeds");
+    };
+    set_guardians(&mut chain, &config, 0);
+
+    assert_eq!(
+        error(approve(&mut chain, CAROL, false, 0)),
+        errors::Error::UnAuthorized
+    );
+    approve(&mut chain, BOB, false, 0).expect("Bob can approve");
+    assert_eq!(error(finalize(&mut chain)), errors::Error::RecoveryNotReady);
+    approve(&mut chain, CAROL, true, 0).expect("The guardian key can approve");
+    assert_eq!(
+        recovery(&chain).map(|recovery| recovery.approvals.len()),
+        Some(2)
+    );
+    assert_eq!(error(finalize(&mut chain)), errors::Error::RecoveryNotReady);
+
+    // The current key cancels the recovery within the window.
+    let message = CancelRecoveryParam {
+        tag: "alice".into(),
+        nonce: 1,
+        entry_point: OwnedEntrypointName::new_unchecked("cancel_recovery".into()),
+        submitter: None,
+        expiry_time: expiry_time(&chain),
+    };
+    let signature = sign(
+        &chain,
+        &tag_key,
+        "get_cancel_recovery_hash",
+        OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+    );
+    let param = CancelRecoveryMessage {
+        signer: registry_key(&tag_key),
+        signature,
+        message,
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "cancel_recovery", param).expect("Cancelling succeeds");
+    assert_eq!(recovery(&chain), None);
+    // The approvals of the cancelled recovery can not be replayed.
+    assert_eq!(
+        error(approve(&mut chain, CAROL, true, 0)),
+        errors::Error::NonceMismatch
+    );
+    let param =
+        OwnedParameter::from_serial(&"alice".to_string()).expect("Parameter within size bounds");
+    let nonce: u64 = view(&chain, "get_recovery_nonce", param)
+        .parse_return_value()
+        .expect("Deserialize nonce");
+    assert_eq!(nonce, 1);
+
+    // Without a cancellation the recovery goes through after the window.
+    approve(&mut chain, BOB, true, 1).expect("Both guardians approve");
+    chain
+        .tick_block_time(Duration::from_days(2))
+        .expect("Block time does not overflow");
+
+    // The approval of a guardian that was removed since does not count.
+    let config = GuardianConfig {
+        guardians: vec![
+            Guardian::Address(Address::Account(CAROL)),
+            Guardian::Key(registry_key(&guardian_key)),
+        ],
+        threshold: 2,
+    };
+    set_guardians(&mut chain, &config, 2);
+    assert_eq!(error(finalize(&mut chain)), errors::Error::RecoveryNotReady);
+    approve(&mut chain, CAROL, false, 1).expect("Carol can approve");
+    finalize(&mut chain).expect("Finalizing succeeds");
+    assert_eq!(get_stats(&chain, &init).recoveries, 1);
+
+    let param =
+        OwnedParameter::from_serial(&"alice".to_string()).expect("Parameter within size bounds");
+    let record: Option<TagRecord> = view(&chain, "resolve", param)
+        .parse_return_value()
+        .expect("Deserialize record");
+    assert_eq!(
+        record.expect("Tag is registered").registry.public_key,
+        registry_key(&new_key)
+    );
+    // The new key takes over the guardians.
+    let param =
+        OwnedParameter::from_serial(&"alice".to_string()).expect("Parameter within size bounds");
+    let guardians: Option<GuardianConfig> = view(&chain, "get_guardians", param)
+        .parse_return_value()
+        .expect("Deserialize guardians");
+    assert_eq!(guardians, Some(config));
+}
+
+/// Test that moving a tag to a new key waits in the pending-change queue,
+/// where the current key can cancel it, until the change delay has passed.
+#[test]
+fn test_rotate_key_after_change_delay() {
+    use ed25519_dalek::Signer;
+
+    let (mut chain, init) = initialize();
+    let rng = &mut rand::thread_rng();
+    let tag_key = ed25519::SigningKey::generate(rng);
+    let new_key = ed25519::SigningKey::generate(rng);
+    let registry_key = |key: &ed25519::SigningKey| {
+        RegistryKey::from(PublicKeyEd25519(key.verifying_key().to_bytes()))
+    };
+    register_tag_with_key(&mut chain, &init, "alice".into(), &tag_key)
+        .expect("Registering succeeds");
+
+    let update = |chain: &mut Chain, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+    };
+    let view = |chain: &Chain, entrypoint: &str, message: OwnedParameter| {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+                    message,
+                },
+            )
+            .expect("Should be able to query the view")
+    };
+    // Signs a message with `key`, using the given hash view.
+    let sign = |chain: &Chain, key: &ed25519::SigningKey, entrypoint: &str, message| {
+        let hash = view(chain, entrypoint, message).return_value;
+        RegistrySignature::from(SignatureEd25519(key.sign(&hash).to_bytes()))
+    };
+    let expiry_time = |chain: &Chain| {
+        chain
+            .block_time()
+            .checked_add(Duration::from_seconds(5))
+            .expect("Expiry does not overflow")
+    };
+    let rotate = |chain: &mut Chain, nonce: u64| {
+        let message = RotateKeyParam {
+            tag: "alice".into(),
+            nonce,
+            new_key: registry_key(&new_key),
+            entry_point: OwnedEntrypointName::new_unchecked("rotate_key".into()),
+            submitter: None,
+            expiry_time: expiry_time(chain),
+        };
+        let param = OwnedParameter::from_serial(&message).expect("Parameter within size bounds");
+        let param = RotateKeyMessage {
+            signer: registry_key(&tag_key),
+            signature: sign(chain, &tag_key, "get_rotate_key_hash", param.clone()),
+            new_key_signature: sign(chain, &new_key, "get_rotate_key_hash", param),
+            message,
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, "rotate_key", param).expect("Rotating the key succeeds");
+    };
+    let execute = |chain: &mut Chain, id: u64| {
+        let param = PendingChangeId {
+            tag: "alice".into(),
+            id,
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, "execute_pending", param)
+    };
+    let pending = |chain: &Chain| -> Vec<PendingChange> {
+        let param = OwnedParameter::from_serial(&"alice".to_string())
+            .expect("Parameter within size bounds");
+        view(chain, "get_pending", param)
+            .parse_return_value()
+            .expect("Deserialize pending changes")
+    };
+
+    rotate(&mut chain, 0);
+    assert_eq!(
+        pending(&chain),
+        [PendingChange {
+            id: 0,
+            change: Change::PublicKey(registry_key(&new_key)),
+            executable_at: chain
+                .block_time()
+                .checked_add(Duration::from_days(1))
+                .expect("Time does not overflow"),
+        }]
+    );
+    let error: errors::Error = execute(&mut chain, 0)
+        .expect_err("The delay has not passed")
+        .parse_return_value()
+        .expect("Deserialize `Error`");
+    assert_eq!(error, errors::Error::ChangeNotReady);
+
+    // The current key cancels the change.
+    let message = CancelPendingParam {
+        tag: "alice".into(),
+        nonce: 1,
+        id: 0,
+        entry_point: OwnedEntrypointName::new_unchecked("cancel_pending".into()),
+        submitter: None,
+        expiry_time: expiry_time(&chain),
+    };
+    let signature = sign(
+        &chain,
+        &tag_key,
+        "get_cancel_pending_hash",
+        OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+    );
+    let param = CancelPendingMessage {
+        signer: registry_key(&tag_key),
+        signature,
+        message,
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, "cancel_pending", param).expect("Cancelling succeeds");
+    assert_eq!(pending(&chain), []);
+
+    rotate(&mut chain, 2);
+    chain
+        .tick_block_time(Duration::from_days(1))
+        .expect("Block time does not overflow");
+    execute(&mut chain, 1).expect("Executing succeeds");
+    assert_eq!(pending(&chain), []);
+
+    let param =
+        OwnedParameter::from_serial(&"alice".to_string()).expect("Parameter within size bounds");
+    let record: Option<TagRecord> = view(&chain, "resolve", param)
+        .parse_return_value()
+        .expect("Deserialize record");
+    assert_eq!(
+        record.expect("Tag is registered").registry.public_key,
+        registry_key(&new_key)
+    );
+}
+
+#[test]
+fn test_moderator_freezes_and_reassigns_tag() {
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new(CAROL, ACC_INITIAL_BALANCE));
+    let rng = &mut rand::thread_rng();
+    let claimant_key = ed25519::SigningKey::generate(rng);
+    register_tag(&mut chain, &init, "alice".into());
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let freeze = |chain: &mut Chain, sender: AccountAddress| {
+        let param = FreezeParam {
+            tag: "alice".into(),
+            case_reference: "case-42".into(),
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, sender, "freeze", param)
+    };
+    let bob_key = ed25519::SigningKey::generate(rng);
+    let transfer = |chain: &mut Chain| {
+        let message =
+            signed_register_message(chain, &init, "alice".into(), "transfer", &bob_key, BOB);
+        let param = TransferParams(vec![concordium_cis2::Transfer {
+            token_id: tag_token_id("alice.ccd"),
+            amount: TokenAmountU8(1),
+            from: ALICE_ADDR,
+            to: Receiver::Account(BOB),
+            data: AdditionalData::from(to_bytes(&message)),
+        }]);
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, ALICE, "transfer", param)
+    };
+    let resolve = |chain: &Chain| -> TagRecord {
+        let param = OwnedParameter::from_serial(&"alice".to_string())
+            .expect("Parameter within size bounds");
+        let record: Option<TagRecord> = chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked("registry.resolve".into()),
+                    message: param,
+                },
+            )
+            .expect("Should be able to query resolve")
+            .parse_return_value()
+            .expect("Deserialize record");
+        record.expect("Tag is registered")
+    };
+
+    // Only moderators can freeze tags, and only the admin appoints them.
+    assert_eq!(error(freeze(&mut chain, BOB)), errors::Error::UnAuthorized);
+    let param = SetModeratorParam {
+        moderator: Address::Account(BOB),
+        enabled: true,
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    assert_eq!(
+        error(update(&mut chain, BOB, "set_moderator", param.clone())),
+        errors::Error::UnAuthorized
+    );
+    update(&mut chain, ALICE, "set_moderator", param).expect("Appointing succeeds");
 
-    let err: errors::Error = update.parse_return_value().expect("Deserialize `Error`");
-    assert_eq!(err, errors::Error::PublicKeyAlreadyExists)
+    let frozen_at = chain.block_time();
+    freeze(&mut chain, BOB).expect("Freezing succeeds");
+    assert_eq!(
+        resolve(&chain).frozen,
+        Some(Freeze {
+            case_reference: "case-42".into(),
+            moderator: Address::Account(BOB),
+            frozen_at,
+        })
+    );
+    let transfer_error: ContractError = transfer(&mut chain)
+        .expect_err("The tag is frozen")
+        .parse_return_value()
+        .expect("Deserialize `ContractError`");
+    assert_eq!(
+        transfer_error,
+        ContractError::Custom(errors::Error::TagFrozen)
+    );
+
+    // The dispute is resolved in favour of the claimant.
+    let message = signed_register_message(
+        &chain,
+        &init,
+        "alice".into(),
+        "reassign",
+        &claimant_key,
+        CAROL,
+    );
+    let param = ReassignParam { message };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    assert_eq!(
+        error(update(&mut chain, CAROL, "reassign", param.clone())),
+        errors::Error::UnAuthorized
+    );
+    update(&mut chain, BOB, "reassign", param).expect("Reassigning succeeds");
+    assert_eq!(get_stats(&chain, &init).reassignments, 1);
+    let record = resolve(&chain);
+    assert_eq!(record.frozen, None);
+    assert_eq!(record.owner, Address::Account(CAROL));
+    assert_eq!(
+        record.registry.public_key,
+        RegistryKey::from(PublicKeyEd25519(claimant_key.verifying_key().to_bytes()))
+    );
+
+    let param =
+        OwnedParameter::from_serial(&"alice".to_string()).expect("Parameter within size bounds");
+    assert_eq!(
+        error(update(&mut chain, BOB, "unfreeze", param)),
+        errors::Error::TagNotFrozen
+    );
+}
+
+#[test]
+fn test_admin_imports_registrations_until_sealed() {
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    let rng = &mut rand::thread_rng();
+    let key = PublicKeyEd25519(
+        ed25519::SigningKey::generate(rng)
+            .verifying_key()
+            .to_bytes(),
+    );
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let entry = |tag: &str| ImportEntry {
+        tag: tag.into(),
+        data: Registry::new(
+            key,
+            ContractAddress {
+                index: 0,
+                subindex: 0,
+            },
+            "AfrixLabs".into(),
+        ),
+        registrar: ALICE_ADDR,
+        owner: Address::Account(BOB),
+        registered_at: Timestamp::from_timestamp_millis(42),
+    };
+    let import = |chain: &mut Chain, sender: AccountAddress, tags: &[&str]| {
+        let param = ImportParam {
+            entries: tags.iter().map(|tag| entry(tag)).collect(),
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(chain, sender, "import", param)
+    };
+
+    assert_eq!(
+        error(import(&mut chain, BOB, &["alice"])),
+        errors::Error::UnAuthorized
+    );
+    import(&mut chain, ALICE, &["alice", "bob"]).expect("Importing succeeds");
+    assert_eq!(
+        error(import(&mut chain, ALICE, &["carol", "alice"])),
+        errors::Error::TagAlreadyExists
+    );
+
+    let param =
+        OwnedParameter::from_serial(&"bob".to_string()).expect("Parameter within size bounds");
+    let entry: RegistryEntry = update(&mut chain, ALICE, "get_key", param)
+        .expect("Tag is imported")
+        .parse_return_value()
+        .expect("Deserialize `RegistryEntry`");
+    assert_eq!(entry.registrar, ALICE_ADDR);
+    assert_eq!(entry.owner, Address::Account(BOB));
+    assert_eq!(entry.registered_at, Timestamp::from_timestamp_millis(42));
+
+    assert_eq!(
+        error(update(
+            &mut chain,
+            BOB,
+            "seal_import",
+            OwnedParameter::empty()
+        )),
+        errors::Error::UnAuthorized
+    );
+    update(&mut chain, ALICE, "seal_import", OwnedParameter::empty()).expect("Sealing succeeds");
+    assert_eq!(
+        error(import(&mut chain, ALICE, &["carol"])),
+        errors::Error::ImportSealed
+    );
+}
+
+#[test]
+fn test_merkle_root_proves_resolutions() {
+    use merkle::MerkleTree;
+
+    let (mut chain, init) = initialize();
+    let view = |chain: &Chain, entrypoint: &str, message: OwnedParameter| {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+                    message,
+                },
+            )
+            .expect("Should be able to query the view")
+    };
+    // Builds the tree from an off-chain copy of the registry.
+    let copy = |chain: &Chain| -> MerkleTree {
+        let param = ListTagsParam {
+            cursor: None,
+            limit: 100,
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        let page: ListTagsResponse = view(chain, "list_tags", param)
+            .parse_return_value()
+            .expect("Deserialize response");
+        page.tags
+            .into_iter()
+            .map(|record| (record.tag, record.registry))
+            .collect()
+    };
+    let root = |chain: &Chain| -> merkle::Hash {
+        view(chain, "merkle_root", OwnedParameter::empty())
+            .parse_return_value()
+            .expect("Deserialize root")
+    };
+
+    assert_eq!(root(&chain), merkle::EMPTY);
+    assert!(copy(&chain).proof("alice.ccd").verify_non_inclusion(
+        &merkle::EMPTY,
+        "alice.ccd",
+        sha256
+    ));
+
+    let tags = ["alice", "bob", "carol", "dave", "erin", "frank", "grace"];
+    for tag in tags {
+        register_tag(&mut chain, &init, tag.into());
+        assert_eq!(root(&chain), copy(&chain).root());
+    }
+
+    let tree = copy(&chain);
+    let root = root(&chain);
+    for tag in tags {
+        let tag = format!("{tag}.ccd");
+        let proof = tree.proof(&tag);
+        let (_, registry) = proof.leaf.clone().expect("Tag is registered");
+        assert!(proof.verify_inclusion(&root, &tag, &registry, sha256));
+        assert!(!proof.verify_non_inclusion(&root, &tag, sha256));
+
+        let other = Registry::new(
+            registry.public_key,
+            registry.contract_address,
+            "Other".into(),
+        );
+        assert!(!proof.verify_inclusion(&root, &tag, &other, sha256));
+    }
+    for tag in ["heidi.ccd", "ivan.ccd", "judy.ccd"] {
+        let proof = tree.proof(tag);
+        assert!(proof.verify_non_inclusion(&root, tag, sha256));
+        // Nor can it show that a registered tag is missing.
+        assert!(!proof.verify_non_inclusion(&root, "alice.ccd", sha256));
+    }
+}
+
+#[test]
+fn test_transfer_ccd_to_tag() {
+    let (mut chain, init) = initialize();
+    let wallet = deploy_wallet_stub(&mut chain);
+    let rng = &mut rand::thread_rng();
+    let key = PublicKeyEd25519(
+        ed25519::SigningKey::generate(rng)
+            .verifying_key()
+            .to_bytes(),
+    );
+
+    let update = |chain: &mut Chain, entrypoint: &str, amount: Amount, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount,
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let entry = |tag: &str, public_key: RegistryKey, contract_address| ImportEntry {
+        tag: tag.into(),
+        data: Registry::new(public_key, contract_address, "AfrixLabs".into()),
+        registrar: ALICE_ADDR,
+        owner: ALICE_ADDR,
+        registered_at: Timestamp::from_timestamp_millis(42),
+    };
+    for entry in [
+        entry("buki", key.into(), wallet),
+        entry("eth", PublicKeyEcdsaSecp256k1([2u8; 33]).into(), wallet),
+        entry("lost", key.into(), ContractAddress::new(42, 0)),
+    ] {
+        let param = ImportParam {
+            entries: vec![entry],
+        };
+        let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+        update(&mut chain, "import", Amount::zero(), param).expect("Importing succeeds");
+    }
+    let transfer_to_tag = |chain: &mut Chain, tag: &str| {
+        let param =
+            OwnedParameter::from_serial(&tag.to_string()).expect("Parameter within size bounds");
+        update(chain, "transfer_to_tag", Amount::from_ccd(5), param)
+    };
+
+    let result = transfer_to_tag(&mut chain, "BUKI").expect("Transfer succeeds");
+    assert_eq!(chain.contract_balance(wallet), Some(Amount::from_ccd(5)));
+    assert_eq!(
+        chain.contract_balance(init.contract_address),
+        Some(Amount::zero())
+    );
+    // The CCD is deposited to the key of the tag.
+    let deposit = result
+        .effective_trace_elements()
+        .find_map(|element| match element {
+            ContractTraceElement::Updated {
+                data:
+                    InstanceUpdatedEvent {
+                        address,
+                        receive_name,
+                        message,
+                        amount,
+                        ..
+                    },
+            } if *address == wallet => Some((receive_name.clone(), message.clone(), *amount)),
+            _ => None,
+        })
+        .expect("Wallet is invoked");
+    assert_eq!(deposit.0.as_receive_name().entrypoint_name(), "depositCcd");
+    assert_eq!(deposit.1.as_ref(), to_bytes(&key));
+    assert_eq!(deposit.2, Amount::from_ccd(5));
+
+    assert_eq!(
+        error(transfer_to_tag(&mut chain, "nobody")),
+        errors::Error::TagDoesNotExist
+    );
+    assert_eq!(
+        error(transfer_to_tag(&mut chain, "eth")),
+        errors::Error::UnsupportedKey
+    );
+    assert_eq!(
+        error(transfer_to_tag(&mut chain, "lost")),
+        errors::Error::InvokeContractError
+    );
+    assert_eq!(chain.contract_balance(wallet), Some(Amount::from_ccd(5)));
+}
+
+#[test]
+fn test_forward_cis2_tokens_to_tag() {
+    let (mut chain, init) = initialize();
+    let wallet = deploy_wallet_stub(&mut chain);
+    let key = PublicKeyEd25519(
+        ed25519::SigningKey::generate(&mut rand::thread_rng())
+            .verifying_key()
+            .to_bytes(),
+    );
+
+    let update = |chain: &mut Chain, entrypoint: &str, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let param = ImportParam {
+        entries: vec![ImportEntry {
+            tag: "buki".into(),
+            data: Registry::new(key, wallet, "AfrixLabs".into()),
+            registrar: ALICE_ADDR,
+            owner: ALICE_ADDR,
+            registered_at: Timestamp::from_timestamp_millis(42),
+        }],
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, "import", param).expect("Importing succeeds");
+    // A token contract notifies the registry of tokens sent to it.
+    let token = deploy_stub(&mut chain, "token", &["transfer"]);
+    let token_id = TokenIdVec(vec![7]);
+    let send = |chain: &mut Chain, tag: &str| {
+        let param = OnReceivingCis2DataParams {
+            token_id: token_id.clone(),
+            amount: TokenAmountU8(5),
+            from: ALICE_ADDR,
+            data: tag.to_string(),
+        };
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked("registry.onReceivingCIS2".into()),
+            message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+        };
+        chain.contract_update(
+            SIGNER,
+            ALICE,
+            Address::Contract(token),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+
+    assert_eq!(
+        error(send(&mut chain, "nobody")),
+        errors::Error::TagDoesNotExist
+    );
+    let result = send(&mut chain, "BUKI").expect("Forwarding succeeds");
+
+    // The tokens are sent on to the wallet of the tag, credited to its key.
+    let forward = result
+        .effective_trace_elements()
+        .find_map(|element| match element {
+            ContractTraceElement::Updated {
+                data:
+                    InstanceUpdatedEvent {
+                        address,
+                        receive_name,
+                        message,
+                        ..
+                    },
+            } if *address == token => Some((receive_name.clone(), message.clone())),
+            _ => None,
+        })
+        .expect("Token contract is invoked");
+    assert_eq!(forward.0.as_receive_name().entrypoint_name(), "transfer");
+    let TransferParams(transfers): TransferParams<ContractTokenId, TokenAmountU8> =
+        from_bytes(forward.1.as_ref()).expect("Deserialize the transfer");
+    let [transfer] = &transfers[..] else {
+        panic!("A single transfer is forwarded");
+    };
+    assert_eq!(
+        (&transfer.token_id, transfer.amount, transfer.from),
+        (
+            &token_id,
+            TokenAmountU8(5),
+            Address::Contract(init.contract_address)
+        )
+    );
+    let Receiver::Contract(address, entrypoint) = &transfer.to else {
+        panic!("The tokens are sent to a contract");
+    };
+    assert_eq!(
+        (*address, entrypoint.as_entrypoint_name().to_string()),
+        (wallet, "depositCis2Tokens".to_string())
+    );
+    assert_eq!(transfer.data.as_ref(), to_bytes(&key));
+
+    // Only a token contract can send tokens.
+    let param = OnReceivingCis2DataParams {
+        token_id: tag_token_id("alice.ccd"),
+        amount: TokenAmountU8(1),
+        from: ALICE_ADDR,
+        data: "buki".to_string(),
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    assert_eq!(
+        error(update(&mut chain, "onReceivingCIS2", param)),
+        errors::Error::UnAuthorized
+    );
+}
+
+#[test]
+fn test_escrow_payments_for_unregistered_tags() {
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    let wallet = deploy_wallet_stub(&mut chain);
+    let key = PublicKeyEd25519(
+        ed25519::SigningKey::generate(&mut rand::thread_rng())
+            .verifying_key()
+            .to_bytes(),
+    );
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, amount, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount,
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let tag_param = |tag: &str| {
+        OwnedParameter::from_serial(&tag.to_string()).expect("Parameter within size bounds")
+    };
+    let escrows = |chain: &mut Chain, tag: &str| -> Vec<Escrow> {
+        update(chain, ALICE, "get_escrows", Amount::zero(), tag_param(tag))
+            .expect("View succeeds")
+            .parse_return_value()
+            .expect("Deserialize `Vec<Escrow>`")
+    };
+
+    update(
+        &mut chain,
+        ALICE,
+        "escrow",
+        Amount::from_ccd(5),
+        tag_param("buki"),
+    )
+    .expect("Paying to an unregistered tag succeeds");
+    update(
+        &mut chain,
+        BOB,
+        "escrow",
+        Amount::from_ccd(2),
+        tag_param("BUKI"),
+    )
+    .expect("Paying to an unregistered tag succeeds");
+    update(
+        &mut chain,
+        BOB,
+        "escrow",
+        Amount::from_ccd(3),
+        tag_param("carol"),
+    )
+    .expect("Paying to an unregistered tag succeeds");
+    assert_eq!(
+        error(update(
+            &mut chain,
+            BOB,
+            "escrow",
+            Amount::zero(),
+            tag_param("carol")
+        )),
+        errors::Error::WrongAmount
+    );
+    let buki_escrows = escrows(&mut chain, "buki");
+    assert_eq!(buki_escrows.len(), 2);
+    assert_eq!(buki_escrows[0].sender, ALICE);
+    assert_eq!(buki_escrows[0].amount, Amount::from_ccd(5));
+
+    // Registering the tag releases its escrow into its wallet.
+    let param = ImportParam {
+        entries: vec![ImportEntry {
+            tag: "buki".into(),
+            data: Registry::new(key, wallet, "AfrixLabs".into()),
+            registrar: ALICE_ADDR,
+            owner: ALICE_ADDR,
+            registered_at: Timestamp::from_timestamp_millis(42),
+        }],
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    update(&mut chain, ALICE, "import", Amount::zero(), param).expect("Importing succeeds");
+    assert_eq!(chain.contract_balance(wallet), Some(Amount::from_ccd(7)));
+    assert!(escrows(&mut chain, "buki").is_empty());
+    assert_eq!(
+        error(update(
+            &mut chain,
+            ALICE,
+            "escrow",
+            Amount::from_ccd(1),
+            tag_param("buki")
+        )),
+        errors::Error::TagAlreadyExists
+    );
+
+    // The escrow of a tag that is not registered in time is refunded.
+    assert_eq!(
+        error(update(
+            &mut chain,
+            BOB,
+            "refund_escrow",
+            Amount::zero(),
+            tag_param("carol")
+        )),
+        errors::Error::EscrowNotRefundable
+    );
+    chain
+        .tick_block_time(Duration::from_days(30))
+        .expect("Block time does not overflow");
+    assert_eq!(
+        error(update(
+            &mut chain,
+            ALICE,
+            "refund_escrow",
+            Amount::zero(),
+            tag_param("carol")
+        )),
+        errors::Error::EscrowDoesNotExist
+    );
+    let balance = chain
+        .account_balance_available(BOB)
+        .expect("Account exists");
+    let refund = update(
+        &mut chain,
+        BOB,
+        "refund_escrow",
+        Amount::zero(),
+        tag_param("carol"),
+    )
+    .expect("Refunding succeeds");
+    assert_eq!(
+        chain.account_balance_available(BOB),
+        Some(balance + Amount::from_ccd(3) - refund.transaction_fee)
+    );
+    assert!(escrows(&mut chain, "carol").is_empty());
+    assert_eq!(
+        chain.contract_balance(init.contract_address),
+        Some(Amount::zero())
+    );
+}
+
+/// Test that a wallet rejecting the deposit of an escrow does not hold up the
+/// registration of the tag, and that the escrow stays refundable.
+#[test]
+fn test_rejecting_wallet_keeps_escrow() {
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    let key = PublicKeyEd25519(
+        ed25519::SigningKey::generate(&mut rand::thread_rng())
+            .verifying_key()
+            .to_bytes(),
+    );
+
+    let update = |chain: &mut Chain, sender: AccountAddress, entrypoint: &str, amount, message| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount,
+            receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+            message,
+        };
+        chain.contract_update(
+            SIGNER,
+            sender,
+            Address::Account(sender),
+            Energy::from(10_000),
+            payload,
+        )
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Update fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+    let tag_param =
+        || OwnedParameter::from_serial(&"dave".to_string()).expect("Parameter within size bounds");
+
+    update(&mut chain, BOB, "escrow", Amount::from_ccd(3), tag_param())
+        .expect("Paying to an unregistered tag succeeds");
+
+    // The registry itself has no `depositCcd` entry point, so it rejects the
+    // deposit as the wallet of the tag.
+    let param = ImportParam {
+        entries: vec![ImportEntry {
+            tag: "dave".into(),
+            data: Registry::new(key, init.contract_address, "AfrixLabs".into()),
+            registrar: ALICE_ADDR,
+            owner: ALICE_ADDR,
+            registered_at: Timestamp::from_timestamp_millis(42),
+        }],
+    };
+    let param = OwnedParameter::from_serial(&param).expect("Parameter within size bounds");
+    let imported =
+        update(&mut chain, ALICE, "import", Amount::zero(), param).expect("Importing succeeds");
+    assert!(imported
+        .events()
+        .all(|(_, events)| events.iter().all(|event| !matches!(
+            event.parse().expect("Deserialize event"),
+            Event::EscrowReleased(_)
+        ))));
+    let escrows: Vec<Escrow> = update(
+        &mut chain,
+        ALICE,
+        "get_escrows",
+        Amount::zero(),
+        tag_param(),
+    )
+    .expect("View succeeds")
+    .parse_return_value()
+    .expect("Deserialize `Vec<Escrow>`");
+    assert_eq!(escrows.len(), 1);
+    assert_eq!(escrows[0].amount, Amount::from_ccd(3));
+
+    // Retrying the deposit fails for as long as the wallet rejects it.
+    assert_eq!(
+        error(update(
+            &mut chain,
+            ALICE,
+            "release_escrow",
+            Amount::zero(),
+            tag_param()
+        )),
+        errors::Error::InvokeContractError
+    );
+
+    // The sender takes the escrow back once it timed out.
+    chain
+        .tick_block_time(Duration::from_days(30))
+        .expect("Block time does not overflow");
+    let balance = chain
+        .account_balance_available(BOB)
+        .expect("Account exists");
+    let refund = update(
+        &mut chain,
+        BOB,
+        "refund_escrow",
+        Amount::zero(),
+        tag_param(),
+    )
+    .expect("Refunding succeeds");
+    assert_eq!(
+        chain.account_balance_available(BOB),
+        Some(balance + Amount::from_ccd(3) - refund.transaction_fee)
+    );
+    assert_eq!(
+        error(update(
+            &mut chain,
+            ALICE,
+            "release_escrow",
+            Amount::zero(),
+            tag_param()
+        )),
+        errors::Error::EscrowDoesNotExist
+    );
+}
+
+/// Measures the average energy of `register`, `get_key` and `get_tag` over 32
+/// tags, for short and for long tags, and checks them against bounds a bit
+/// above the figures in the README. Run it with `--ignored --nocapture` to
+/// print the figures.
+#[test]
+#[ignore = "benchmark"]
+fn test_energy_benchmark() {
+    let view = |chain: &Chain, init: &ContractInitSuccess, entrypoint: &str, message| {
+        chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    address: init.contract_address,
+                    amount: Amount::zero(),
+                    receive_name: OwnedReceiveName::new_unchecked(format!("registry.{entrypoint}")),
+                    message,
+                },
+            )
+            .expect("Query succeeds")
+            .energy_used
+            .energy
+    };
+    for prefix in ["user", "a-rather-long-brand-name-for-a-wallet-user"] {
+        let (mut chain, init) = initialize();
+        let rng = &mut rand::thread_rng();
+        let mut register = Vec::new();
+        let mut get_key = Vec::new();
+        let mut get_tag = Vec::new();
+        for i in 0..32 {
+            let tag = format!("{prefix}{i:02}");
+            let signing_key = ed25519::SigningKey::generate(rng);
+            let update = register_tag_with_key(&mut chain, &init, tag.clone(), &signing_key)
+                .expect("Registering succeeds");
+            register.push(update.energy_used.energy);
+            let param = OwnedParameter::from_serial(&tag).expect("Parameter within size bounds");
+            get_key.push(view(&chain, &init, "get_key", param));
+            let key = RegistryKey::from(PublicKeyEd25519(signing_key.verifying_key().to_bytes()));
+            let param = OwnedParameter::from_serial(&key).expect("Parameter within size bounds");
+            get_tag.push(view(&chain, &init, "get_tag", param));
+        }
+        let average = |energy: &[u64]| energy.iter().sum::<u64>() / energy.len() as u64;
+        let (register, get_key, get_tag) =
+            (average(&register), average(&get_key), average(&get_tag));
+        println!("{prefix}: register {register} get_key {get_key} get_tag {get_tag}");
+        assert!(register <= 6_000, "register costs {register} NRG");
+        assert!(get_key <= 1_100, "get_key costs {get_key} NRG");
+        assert!(get_tag <= 1_100, "get_tag costs {get_tag} NRG");
+    }
+}
+
+#[test]
+fn test_signed_messages_bind_entry_point_and_submitter() {
+    use ed25519_dalek::Signer;
+
+    let (mut chain, init) = initialize();
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    let signing_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    let register = |chain: &mut Chain, sender: AccountAddress, message: &RegisterMessage| {
+        let payload = UpdateContractPayload {
+            address: init.contract_address,
+            amount: Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked("registry.register".to_string()),
+            message: OwnedParameter::from_serial(message).expect("Parameter within size bounds"),
+        };
+        chain.contract_update(SIGNER, sender, sender.into(), Energy::from(10_000), payload)
+    };
+    let error = |result: Result<ContractInvokeSuccess, ContractInvokeError>| -> errors::Error {
+        result
+            .expect_err("Registering fails")
+            .parse_return_value()
+            .expect("Deserialize `Error`")
+    };
+
+    // A registration signed for `bid` can not be used with `register`.
+    let message =
+        signed_register_message(&chain, &init, "alice".into(), "bid", &signing_key, ALICE);
+    assert_eq!(
+        error(register(&mut chain, ALICE, &message)),
+        errors::Error::WrongEntryPoint
+    );
+
+    // A registration that names its submitter can only be sent by it.
+    let mut message = signed_register_message(
+        &chain,
+        &init,
+        "alice".into(),
+        "register",
+        &signing_key,
+        ALICE,
+    );
+    message.message.submitter = Some(Address::Account(BOB));
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "registry.get_param_hash".to_string(),
+                ),
+                message: OwnedParameter::from_serial(&message.message)
+                    .expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query get_param_hash");
+    message.signature = SignatureEd25519(signing_key.sign(&invoke.return_value).to_bytes()).into();
+    assert_eq!(
+        error(register(&mut chain, ALICE, &message)),
+        errors::Error::UnAuthorized
+    );
+    let update = register(&mut chain, BOB, &message).expect("The submitter can register");
+
+    // The token goes to the signed owner, not to the submitter.
+    let events: Vec<Event> = update
+        .events()
+        .flat_map(|(_, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect();
+    assert!(matches!(
+        &events[..],
+        [
+            Event::Register(RegisterEvent {
+                registrar: Address::Account(BOB),
+                ..
+            }),
+            Event::Mint(MintEvent {
+                owner: Address::Account(ALICE),
+                ..
+            }),
+            Event::TokenMetadata(_),
+        ]
+    ));
+}
+
+#[test]
+fn test_register_message_hash_vectors() {
+    let (chain, init) = initialize();
+    let vectors: serde_json::Value =
+        serde_json::from_str(include_str!("vectors/register_message_hash.json"))
+            .expect("Valid test vectors");
+    let bytes = |value: &serde_json::Value| hex::decode(value.as_str().expect("Hex string"));
+
+    for vector in vectors.as_array().expect("List of test vectors") {
+        let genesis_hash: [u8; 32] = bytes(&vector["genesis_hash"])
+            .expect("Valid genesis hash")
+            .try_into()
+            .expect("Genesis hash of 32 bytes");
+        let contract_address = ContractAddress {
+            index: vector["contract_address"]["index"].as_u64().expect("Index"),
+            subindex: vector["contract_address"]["subindex"]
+                .as_u64()
+                .expect("Subindex"),
+        };
+        let param: RegisterParam =
+            from_bytes(&bytes(&vector["param"]).expect("Valid param")).expect("Deserialize param");
+        let hash = bytes(&vector["hash"]).expect("Valid hash");
+
+        // The offline hash matches the test vector.
+        assert_eq!(
+            calculate_register_message_hash(&genesis_hash, contract_address, &param).as_slice(),
+            hash.as_slice(),
+            "{}",
+            vector["description"]
+        );
+
+        // So does the hash of the contract, for the vectors of its instance.
+        if genesis_hash != TESTNET_GENESIS_HASH || contract_address != init.contract_address {
+            continue;
+        }
+        let invoke = chain
+            .contract_invoke(
+                ALICE,
+                ALICE_ADDR,
+                Energy::from(10_000),
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address: init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked(
+                        "registry.get_param_hash".to_string(),
+                    ),
+                    message: OwnedParameter::from_serial(&param)
+                        .expect("Parameter within size bounds"),
+                },
+            )
+            .expect("Should be able to query get_param_hash");
+        assert_eq!(invoke.return_value, hash, "{}", vector["description"]);
+    }
 }
 
 /// Helper method for initializing the contract.
@@ -483,23 +3663,85 @@ fn initialize() -> (Chain, ContractInitSuccess) {
 ///  - Initializes the contract and create a tag for alice.
 ///  - Returns the [`Chain`] and the [`ContractInitSuccess`]
 fn initialize_chain_and_create_tag(tag: String) -> (Chain, ContractInitSuccess, PublicKeyEd25519) {
-    use ed25519_dalek::{Signer, SigningKey};
+    let (mut chain, init) = initialize();
+    let alice_public_key = register_tag(&mut chain, &init, tag);
+    (chain, init, alice_public_key)
+}
 
-    // Initialize the test chain.
-    let mut chain = Chain::new();
+/// Helper method for querying the global counters of the registry.
+fn get_stats(chain: &Chain, init: &ContractInitSuccess) -> StatsResponse {
+    let param = StatsParam {
+        providers: Vec::new(),
+        namespaces: Vec::new(),
+    };
+    chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                address: init.contract_address,
+                amount: Amount::zero(),
+                receive_name: OwnedReceiveName::new_unchecked("registry.stats".to_string()),
+                message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Querying stats succeeds")
+        .parse_return_value()
+        .expect("Deserialize response")
+}
 
-    // Create the test account.
-    chain.create_account(Account::new(ALICE, ACC_INITIAL_BALANCE));
+/// Helper method for deploying a stand-in for a CIS-5 wallet, which accepts
+/// any amount of CCD through its `depositCcd` entry point, and any CIS-2
+/// tokens through its `depositCis2Tokens` entry point.
+///
+/// Returns the [`ContractAddress`] of the wallet.
+fn deploy_wallet_stub(chain: &mut Chain) -> ContractAddress {
+    deploy_stub(chain, "wallet", &["depositCcd", "depositCis2Tokens"])
+}
 
-    // Load the module.
-    let module = module_load_v1("./dist/module.wasm.v1").expect("Module exists at path");
-    // Deploy the module.
+/// Deploys and initializes a stub of the contract `name` whose `entrypoints`
+/// accept every call.
+fn deploy_stub(chain: &mut Chain, name: &str, entrypoints: &[&str]) -> ContractAddress {
+    // A module exporting `init_<name>` and `<name>.<entrypoint>` for every
+    // entry point, all of type `(i64) -> i32` and returning 0 for success.
+    fn export(name: &str, index: u8) -> Vec<u8> {
+        let mut bytes = vec![name.len() as u8];
+        bytes.extend(name.as_bytes());
+        bytes.extend([0x00, index]);
+        bytes
+    }
+    fn section(id: u8, content: Vec<u8>) -> Vec<u8> {
+        let mut bytes = vec![id, content.len() as u8];
+        bytes.extend(content);
+        bytes
+    }
+    let functions = entrypoints.len() as u8 + 1;
+    let mut exports = vec![functions];
+    exports.extend(export(&format!("init_{name}"), 0));
+    for (index, entrypoint) in (1..).zip(entrypoints) {
+        exports.extend(export(&format!("{name}.{entrypoint}"), index));
+    }
+    let mut wasm = b"\0asm".to_vec();
+    wasm.extend([1, 0, 0, 0]);
+    wasm.extend(section(1, vec![1, 0x60, 1, 0x7e, 1, 0x7f]));
+    let mut types = vec![functions];
+    types.resize(usize::from(functions) + 1, 0);
+    wasm.extend(section(3, types));
+    wasm.extend(section(7, exports));
+    let mut code = vec![functions];
+    for _ in 0..functions {
+        code.extend([4, 0, 0x41, 0, 0x0b]);
+    }
+    wasm.extend(section(10, code));
+
+    let path = std::env::temp_dir().join(format!("{name}-stub-{}.wasm", std::process::id()));
+    std::fs::write(&path, wasm).expect("Write the module");
+    let module = module_load_v1_raw(&path).expect("Module exists at path");
     let deployment = chain
         .module_deploy_v1(SIGNER, ALICE, module)
         .expect("Deploy valid module");
-
-    // Initialize the contract.
-    let init = chain
+    chain
         .contract_init(
             SIGNER,
             ALICE,
@@ -507,19 +3749,55 @@ fn initialize_chain_and_create_tag(tag: String) -> (Chain, ContractInitSuccess,
             InitContractPayload {
                 amount: Amount::zero(),
                 mod_ref: deployment.module_reference,
-                init_name: OwnedContractName::new_unchecked("init_registry".to_string()),
+                init_name: OwnedContractName::new_unchecked(format!("init_{name}")),
                 param: OwnedParameter::empty(),
             },
         )
-        .expect("Initializing contract");
+        .expect("Initializing contract")
+        .contract_address
+}
 
-    let rng = &mut rand::thread_rng();
+/// Helper method for registering a tag for a freshly generated key.
+///
+/// Returns the [`PublicKeyEd25519`] that the tag was registered for.
+fn register_tag(chain: &mut Chain, init: &ContractInitSuccess, tag: String) -> PublicKeyEd25519 {
+    let signing_key = ed25519::SigningKey::generate(&mut rand::thread_rng());
+    register_tag_with_key(chain, init, tag, &signing_key).expect("failed to update contract");
+    PublicKeyEd25519(signing_key.verifying_key().to_bytes())
+}
 
-    // Construct signing key.
-    let signing_key = SigningKey::generate(rng);
-    let alice_public_key = PublicKeyEd25519(signing_key.verifying_key().to_bytes());
+/// Helper method for registering a tag for the key of `signing_key`, which
+/// also signs the registration.
+fn register_tag_with_key(
+    chain: &mut Chain,
+    init: &ContractInitSuccess,
+    tag: String,
+    signing_key: &ed25519::SigningKey,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    let message = signed_register_message(chain, init, tag, "register", signing_key, ALICE);
+    let payload = UpdateContractPayload {
+        address: init.contract_address,
+        amount: Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("registry.register".to_string()),
+        message: OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
+    };
+
+    chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
+}
+
+/// Helper method for building a `RegisterMessage` for the key of
+/// `signing_key` and the `entry_point`, signed by that key.
+fn signed_register_message(
+    chain: &Chain,
+    init: &ContractInitSuccess,
+    tag: String,
+    entry_point: &str,
+    signing_key: &ed25519::SigningKey,
+    owner: AccountAddress,
+) -> RegisterMessage {
+    let public_key = PublicKeyEd25519(signing_key.verifying_key().to_bytes());
     let registry = Registry::new(
-        alice_public_key,
+        public_key,
         ContractAddress {
             index: 0,
             subindex: 0,
@@ -529,8 +3807,27 @@ fn initialize_chain_and_create_tag(tag: String) -> (Chain, ContractInitSuccess,
     let param = RegisterParam {
         tag,
         data: registry,
-        expiry_time: Timestamp::from_timestamp_millis(5000),
+        owner: Address::Account(owner),
+        entry_point: OwnedEntrypointName::new_unchecked(entry_point.into()),
+        submitter: None,
+        expiry_time: chain
+            .block_time()
+            .checked_add(Duration::from_seconds(5))
+            .expect("Expiry does not overflow"),
     };
+    sign_register_param(chain, init, param, signing_key)
+}
+
+/// Signs `param` with `signing_key`, which must be the key of its record.
+fn sign_register_param(
+    chain: &Chain,
+    init: &ContractInitSuccess,
+    param: RegisterParam,
+    signing_key: &ed25519::SigningKey,
+) -> RegisterMessage {
+    use ed25519_dalek::Signer;
+
+    let public_key = PublicKeyEd25519(signing_key.verifying_key().to_bytes());
     // Get the message hash to be signed.
     let invoke = chain
         .contract_invoke(
@@ -550,21 +3847,68 @@ fn initialize_chain_and_create_tag(tag: String) -> (Chain, ContractInitSuccess,
         .expect("Should be able to query getCcdWithdrawMessageHash");
     let signature = signing_key.sign(&invoke.return_value);
 
-    let message = RegisterMessage {
-        signer: alice_public_key,
-        signature: SignatureEd25519(signature.to_bytes()),
+    RegisterMessage {
+        signer: public_key.into(),
+        signature: SignatureEd25519(signature.to_bytes()).into(),
         message: param,
-    };
-    let payload = UpdateContractPayload {
-        address: init.contract_address,
-        amount: Amount::zero(),
-        receive_name: OwnedReceiveName::new_unchecked("registry.register".to_string()),
-        message: OwnedParameter::from_serial(&message).expect("Parameter within size bounds"),
-    };
+    }
+}
 
-    chain
-        .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), payload)
-        .expect("failed to update contract");
+/// Returns a `permit` message of the account `signer`, signed with its
+/// `account_key`, to invoke `entry_point` with `payload`.
+fn signed_permit(
+    chain: &Chain,
+    init: &ContractInitSuccess,
+    signer: AccountAddress,
+    account_key: &ed25519::SigningKey,
+    nonce: u64,
+    entry_point: &str,
+    payload: Vec<u8>,
+) -> PermitParam {
+    use ed25519_dalek::Signer;
 
-    (chain, init, alice_public_key)
+    let mut param = PermitParam {
+        signature: AccountSignatures {
+            sigs: BTreeMap::new(),
+        },
+        signer,
+        message: PermitMessage {
+            contract_address: init.contract_address,
+            nonce,
+            timestamp: chain
+                .block_time()
+                .checked_add(Duration::from_seconds(5))
+                .expect("Expiry does not overflow"),
+            entry_point: OwnedEntrypointName::new_unchecked(entry_point.into()),
+            payload,
+        },
+    };
+    let invoke = chain
+        .contract_invoke(
+            ALICE,
+            ALICE_ADDR,
+            Energy::from(10_000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address: init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "registry.viewMessageHash".to_string(),
+                ),
+                message: OwnedParameter::from_serial(&param).expect("Parameter within size bounds"),
+            },
+        )
+        .expect("Should be able to query viewMessageHash");
+    let signature = account_key.sign(&invoke.return_value);
+    param.signature = AccountSignatures {
+        sigs: BTreeMap::from([(
+            0,
+            CredentialSignatures {
+                sigs: BTreeMap::from([(
+                    0,
+                    Signature::Ed25519(SignatureEd25519(signature.to_bytes())),
+                )]),
+            },
+        )]),
+    };
+    param
 }